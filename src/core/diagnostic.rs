@@ -0,0 +1,220 @@
+////////////////////////////////////////////////////////////////////////////
+/// The diagnostics of the errors of the command line : what is wrong,   ///
+/// the words where it has been found and how to fix it, ex:             ///
+///   unknown option --keep-ordre                                        ///
+///     --keep-ordre echo ::: a                                          ///
+///     ^^^^^^^^^^^^                                                     ///
+///   did you mean --keep-order?                                         ///
+////////////////////////////////////////////////////////////////////////////
+use std::fmt;
+
+/// The description of an error, with the span of the offending words in the line
+/// where they have been found when it is known, and the spans of the other words
+/// involved in it (ex: the first of two options which can't be used together).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: String,
+    pub span: Option<(usize, usize)>,
+    pub related: Box<[(usize, usize)]>,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /**
+     * Create a diagnostic without any span.
+     * # Arguments
+     * - `message` - what is wrong
+     */
+    pub fn new(message: String) -> Diagnostic {
+        Diagnostic {
+            message,
+            line: String::new(),
+            span: None,
+            related: Box::new([]),
+            suggestion: None,
+        }
+    }
+
+    /**
+     * Set the span of the offending words, the byte offsets in the line.
+     * # Arguments
+     * - `start` - the offset of the first byte
+     * - `end` - the offset after the last byte
+     */
+    pub fn at(mut self, start: usize, end: usize) -> Diagnostic {
+        self.span = Some((start, end));
+        self
+    }
+
+    /**
+     * Add the span of other words involved in the error, they are underlined with the offending ones.
+     * # Arguments
+     * - `start` - the offset of the first byte
+     * - `end` - the offset after the last byte
+     */
+    pub fn also_at(mut self, start: usize, end: usize) -> Diagnostic {
+        let mut related = self.related.into_vec();
+        related.push((start, end));
+        self.related = related.into_boxed_slice();
+        self
+    }
+
+    /**
+     * Set the line containing the span, if it isn't set yet : the spans are found
+     * before the whole line is known.
+     * # Arguments
+     * - `line` - the command line
+     */
+    pub fn in_line(mut self, line: &str) -> Diagnostic {
+        if self.line.is_empty() {
+            self.line = String::from(line);
+        }
+        self
+    }
+
+    /**
+     * Set how to fix the error, displayed as "did you mean ...?".
+     * # Arguments
+     * - `suggestion` - the words to use instead of the offending ones
+     */
+    pub fn suggest(mut self, suggestion: &str) -> Diagnostic {
+        self.suggestion = Some(String::from(suggestion));
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(span) = self.span {
+            let mut spans: Vec<(usize, usize)> = self.related.to_vec();
+            spans.push(span);
+            spans.sort_unstable();
+            // the carets are below the characters of the spans, at least one for an empty span
+            let mut carets = String::new();
+            let mut underlined = 0;
+            for (start, end) in spans {
+                let start = start.min(self.line.len()).max(underlined);
+                let end = end.clamp(start, self.line.len());
+                if let (Some(before), Some(spanned)) =
+                    (self.line.get(underlined..start), self.line.get(start..end))
+                {
+                    carets.push_str(&" ".repeat(before.chars().count()));
+                    carets.push_str(&"^".repeat(spanned.chars().count().max(1)));
+                    // the caret of an empty span is below the next character
+                    underlined = match self.line[end..].chars().next() {
+                        Some(next) if start == end => end + next.len_utf8(),
+                        _ => end,
+                    };
+                }
+            }
+            if !carets.is_empty() {
+                write!(f, "\n    {}\n    {}", self.line, carets)?;
+            }
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\ndid you mean {}?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Return the candidate closest to the word, if it is close enough to be a typo of it
+ * (ex: "--keep-order" for "--keep-ordre").
+ * # Arguments
+ * - `word` - the unknown word
+ * - `candidates` - the known words
+ */
+pub fn closest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let word = word.to_ascii_lowercase();
+    // a third of the characters can be wrong, at least 2 (ex: "--jbos" for "--jobs")
+    let max_distance = (word.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|candidate| (distance(&word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/**
+ * Private function.
+ *
+ * Return the number of characters to insert, remove, replace or swap with the next one
+ * to change a word into another (the optimal string alignment distance).
+ */
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i characters of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut best = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = best;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic::new(String::from("unknown option --keep-ordre"))
+            .at(0, 12)
+            .in_line("--keep-ordre echo ::: a")
+            .suggest("--keep-order");
+        assert_eq!(
+            diagnostic.to_string(),
+            "unknown option --keep-ordre\n    --keep-ordre echo ::: a\n    ^^^^^^^^^^^^\ndid you mean --keep-order?"
+        );
+        // the line is kept once set, an empty span has a caret
+        let diagnostic = Diagnostic::new(String::from("unexpected end"))
+            .in_line("echo 'é")
+            .in_line("another line")
+            .at(8, 8);
+        assert_eq!(
+            diagnostic.to_string(),
+            "unexpected end\n    echo 'é\n           ^"
+        );
+        assert_eq!(
+            Diagnostic::new(String::from("no data")).to_string(),
+            "no data"
+        );
+        // the related words are underlined too
+        let diagnostic = Diagnostic::new(String::from("conflict"))
+            .at(14, 32)
+            .also_at(0, 13)
+            .in_line("--server 8000 --client host 8000 echo");
+        assert_eq!(
+            diagnostic.to_string(),
+            "conflict\n    --server 8000 --client host 8000 echo\n    ^^^^^^^^^^^^^ ^^^^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let options = ["--jobs", "--joblog", "--keep-order", "--dry-run"];
+        assert_eq!(closest("--keep-ordre", &options), Some("--keep-order"));
+        assert_eq!(closest("--JBOS", &options), Some("--jobs"));
+        assert_eq!(closest("--dryrun", &options), Some("--dry-run"));
+        assert_eq!(closest("--verbose", &options), None);
+    }
+}
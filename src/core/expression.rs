@@ -0,0 +1,480 @@
+////////////////////////////////////////////////////////////////////////////
+/// The expressions of the replacement strings `{= ... =}`, a small and  ///
+/// safe language transforming the value of a job (like the Perl code    ///
+/// of GNU parallel). An expression is a list of statements separated by ///
+/// ";", each one changes the value given by the previous one :          ///
+///   s/REGEX/REPLACEMENT/FLAGS  regex substitution, flags g and i       ///
+///   uc | lc                    upper case | lower case                 ///
+///   substr(START[, LENGTH])    substring, negative numbers count from  ///
+///                              the end                                 ///
+///   pad(WIDTH[, CHAR])         pad on the left up to WIDTH characters  ///
+///   ARITHMETIC                 the value becomes the result, with      ///
+///                              + - * / % ( ), integers, $_ (the value) ///
+///                              and $# (the sequence number of the job) ///
+/// Only the parse can fail : like Perl, a value which isn't a number    ///
+/// counts as the number it starts with (or 0), and a division by zero  ///
+/// gives 0 instead of stopping the execution.                           ///
+////////////////////////////////////////////////////////////////////////////
+use regex::{Regex, RegexBuilder};
+
+/// A parsed expression, evaluated for each job.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    statements: Vec<Statement>,
+}
+
+/// A statement of an expression, it changes the value.
+#[derive(Clone, Debug)]
+enum Statement {
+    Substitute {
+        regex: Box<Regex>,
+        replacement: String,
+        global: bool,
+    },
+    Upper,
+    Lower,
+    Substr(i64, Option<i64>),
+    Pad(usize, char),
+    Arithmetic(Arithmetic),
+}
+
+/// An arithmetic expression on integers.
+#[derive(Clone, Debug)]
+enum Arithmetic {
+    Number(i64),
+    Value,
+    Seq,
+    Negative(Box<Arithmetic>),
+    Binary(Box<Arithmetic>, char, Box<Arithmetic>),
+}
+
+/**
+ * Private structure.
+ *
+ * Reads the source of an expression character by character :
+ * - `chars : Vec<char>` - the source
+ * - `position : usize` - the next character to read
+ */
+struct Cursor {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn read(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips the spaces and the given character if it is next, return true if it was.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_spaces();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}' expected", expected)))
+        }
+    }
+
+    /// Returns the next word made of letters.
+    fn word(&mut self) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /// Returns the next integer, with an optional sign.
+    fn integer(&mut self) -> Result<i64, String> {
+        self.skip_spaces();
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits
+            .parse::<i64>()
+            .map_err(|_| self.error("an integer expected"))
+    }
+
+    /// Returns the text until the delimiter, which can be escaped by a backslash.
+    fn delimited(&mut self, delimiter: char) -> Result<String, String> {
+        let mut text = String::new();
+        loop {
+            match self.read() {
+                None => return Err(self.error(&format!("'{}' expected", delimiter))),
+                Some(c) if c == delimiter => return Ok(text),
+                Some('\\') if self.peek() == Some(delimiter) => {
+                    text.push(delimiter);
+                    self.position += 1;
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        let source: String = self.chars.iter().collect();
+        format!(
+            "{} at character {} of the expression \"{}\"",
+            message,
+            self.position.min(self.chars.len()) + 1,
+            source
+        )
+    }
+}
+
+/**
+ * Parse an expression, the source is what is between "{=" and "=}".
+ * Return the reason why it is invalid in case of error.
+ */
+pub fn parse(source: &str) -> Result<Expression, String> {
+    let mut cursor = Cursor {
+        chars: source.chars().collect(),
+        position: 0,
+    };
+    let mut statements = Vec::new();
+    loop {
+        cursor.skip_spaces();
+        if cursor.peek().is_none() {
+            return Ok(Expression { statements });
+        }
+        statements.push(parse_statement(&mut cursor)?);
+        cursor.skip_spaces();
+        if cursor.peek().is_some() {
+            cursor.expect(';')?;
+        }
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Parse the statement starting at the cursor.
+ */
+fn parse_statement(cursor: &mut Cursor) -> Result<Statement, String> {
+    let start = cursor.position;
+    match cursor.word().as_str() {
+        // arithmetic
+        "" => Ok(Statement::Arithmetic(parse_sum(cursor)?)),
+        "s" => {
+            let delimiter = match cursor.read() {
+                Some(c) if !c.is_alphanumeric() && !c.is_whitespace() => c,
+                _ => return Err(cursor.error("a delimiter expected after s")),
+            };
+            let pattern = cursor.delimited(delimiter)?;
+            let replacement = cursor.delimited(delimiter)?;
+            let flags = cursor.word();
+            if let Some(flag) = flags.chars().find(|flag| *flag != 'g' && *flag != 'i') {
+                return Err(cursor.error(&format!("unknown flag '{}'", flag)));
+            }
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(flags.contains('i'))
+                .build()
+                .map_err(|error| cursor.error(&format!("invalid regex ({})", error)))?;
+            Ok(Statement::Substitute {
+                regex: Box::new(regex),
+                replacement: perl_replacement(&replacement),
+                global: flags.contains('g'),
+            })
+        }
+        "uc" => Ok(Statement::Upper),
+        "lc" => Ok(Statement::Lower),
+        "substr" => {
+            cursor.expect('(')?;
+            let start = cursor.integer()?;
+            let length = if cursor.eat(',') {
+                Some(cursor.integer()?)
+            } else {
+                None
+            };
+            cursor.expect(')')?;
+            Ok(Statement::Substr(start, length))
+        }
+        "pad" => {
+            cursor.expect('(')?;
+            let width = cursor.integer()?;
+            let fill = if cursor.eat(',') {
+                cursor.skip_spaces();
+                match cursor.read() {
+                    Some(c) if c != ')' => c,
+                    _ => return Err(cursor.error("a character expected")),
+                }
+            } else {
+                ' '
+            };
+            cursor.expect(')')?;
+            Ok(Statement::Pad(width.max(0) as usize, fill))
+        }
+        word => {
+            cursor.position = start;
+            Err(cursor.error(&format!("unknown statement \"{}\"", word)))
+        }
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Parse an addition or a subtraction (the lowest precedence).
+ */
+fn parse_sum(cursor: &mut Cursor) -> Result<Arithmetic, String> {
+    let mut left = parse_product(cursor)?;
+    loop {
+        cursor.skip_spaces();
+        match cursor.peek() {
+            Some(operator) if operator == '+' || operator == '-' => {
+                cursor.position += 1;
+                let right = parse_product(cursor)?;
+                left = Arithmetic::Binary(Box::new(left), operator, Box::new(right));
+            }
+            _ => return Ok(left),
+        }
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Parse a multiplication, a division or a remainder.
+ */
+fn parse_product(cursor: &mut Cursor) -> Result<Arithmetic, String> {
+    let mut left = parse_factor(cursor)?;
+    loop {
+        cursor.skip_spaces();
+        match cursor.peek() {
+            Some(operator) if operator == '*' || operator == '/' || operator == '%' => {
+                cursor.position += 1;
+                let right = parse_factor(cursor)?;
+                left = Arithmetic::Binary(Box::new(left), operator, Box::new(right));
+            }
+            _ => return Ok(left),
+        }
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Parse a number, a variable, a negation or a parenthesized expression.
+ */
+fn parse_factor(cursor: &mut Cursor) -> Result<Arithmetic, String> {
+    cursor.skip_spaces();
+    match cursor.peek() {
+        Some('(') => {
+            cursor.position += 1;
+            let inner = parse_sum(cursor)?;
+            cursor.expect(')')?;
+            Ok(inner)
+        }
+        Some('-') => {
+            cursor.position += 1;
+            Ok(Arithmetic::Negative(Box::new(parse_factor(cursor)?)))
+        }
+        Some('$') => {
+            cursor.position += 1;
+            match cursor.read() {
+                Some('_') => Ok(Arithmetic::Value),
+                Some('#') => Ok(Arithmetic::Seq),
+                _ => {
+                    cursor.position -= 1;
+                    Err(cursor.error("unknown variable, $_ or $# expected"))
+                }
+            }
+        }
+        Some(c) if c.is_ascii_digit() => Ok(Arithmetic::Number(cursor.integer()?)),
+        _ => Err(cursor.error("a number, $_ or $# expected")),
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Converts the groups of a Perl replacement ($1) into the syntax of the regex crate (${1}),
+ * so the group can be followed by letters.
+ */
+fn perl_replacement(replacement: &str) -> String {
+    let group = Regex::new(r"\$(\d+)").unwrap();
+    group.replace_all(replacement, "$${${1}}").into_owned()
+}
+
+impl Expression {
+    /**
+     * Return the value transformed by the expression.
+     * # Arguments
+     * - `value` - the value of the job given to the expression
+     * - `seq` - the sequence number of the job, from 1
+     */
+    pub fn eval(&self, value: &str, seq: usize) -> String {
+        let mut value = String::from(value);
+        for statement in &self.statements {
+            value = match statement {
+                Statement::Substitute {
+                    regex,
+                    replacement,
+                    global,
+                } => {
+                    if *global {
+                        regex.replace_all(&value, replacement.as_str()).into_owned()
+                    } else {
+                        regex.replace(&value, replacement.as_str()).into_owned()
+                    }
+                }
+                Statement::Upper => value.to_uppercase(),
+                Statement::Lower => value.to_lowercase(),
+                Statement::Substr(start, length) => substr(&value, *start, *length),
+                Statement::Pad(width, fill) => {
+                    let missing = width.saturating_sub(value.chars().count());
+                    std::iter::repeat_n(*fill, missing)
+                        .chain(value.chars())
+                        .collect()
+                }
+                Statement::Arithmetic(arithmetic) => {
+                    arithmetic.eval(numify(&value), seq as i64).to_string()
+                }
+            };
+        }
+        value
+    }
+}
+
+impl Arithmetic {
+    /**
+     * Private function.
+     *
+     * Return the result of the operation, the overflows wrap around.
+     */
+    fn eval(&self, value: i64, seq: i64) -> i64 {
+        match self {
+            Arithmetic::Number(number) => *number,
+            Arithmetic::Value => value,
+            Arithmetic::Seq => seq,
+            Arithmetic::Negative(inner) => inner.eval(value, seq).wrapping_neg(),
+            Arithmetic::Binary(left, operator, right) => {
+                let left = left.eval(value, seq);
+                let right = right.eval(value, seq);
+                match operator {
+                    '+' => left.wrapping_add(right),
+                    '-' => left.wrapping_sub(right),
+                    '*' => left.wrapping_mul(right),
+                    _ if right == 0 => 0,
+                    '/' => left.wrapping_div(right),
+                    _ => left.wrapping_rem(right),
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Return the substring of the value, by characters, like the substr of Perl :
+ * a negative start counts from the end, a negative length leaves characters at the end.
+ */
+fn substr(value: &str, start: i64, length: Option<i64>) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len() as i64;
+    let start = if start < 0 { len + start } else { start }.clamp(0, len);
+    let end = match length {
+        None => len,
+        Some(length) if length < 0 => len + length,
+        Some(length) => start.saturating_add(length),
+    }
+    .clamp(start, len);
+    chars[start as usize..end as usize].iter().collect()
+}
+
+/**
+ * Private function.
+ *
+ * Return the number at the start of the value (like Perl), 0 if it doesn't start with a number.
+ */
+fn numify(value: &str) -> i64 {
+    let value = value.trim_start();
+    let end = value
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && (*c == '-' || *c == '+'))))
+        .map_or(value.len(), |(i, _)| i);
+    value[..end].parse().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, value: &str, seq: usize) -> String {
+        parse(source).unwrap().eval(value, seq)
+    }
+
+    #[test]
+    fn substitution() {
+        assert_eq!(eval("s/foo/bar/", "foo foo", 1), "bar foo");
+        assert_eq!(eval("s/foo/bar/g", "foo foo", 1), "bar bar");
+        assert_eq!(eval("s/FOO/bar/i", "foo", 1), "bar");
+        assert_eq!(eval(r"s/(\w+)\.(\w+)/$2.$1x/", "a.b", 1), "b.ax");
+        assert_eq!(eval(r"s:/:\::g", "a/b/c", 1), "a:b:c");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("uc", "abc", 1), "ABC");
+        assert_eq!(eval("lc; s/a/x/", "ABC", 1), "xbc");
+        assert_eq!(eval("substr(1, 2)", "abcdef", 1), "bc");
+        assert_eq!(eval("substr(-2)", "abcdef", 1), "ef");
+        assert_eq!(eval("substr(0, -4)", "abcdef", 1), "ab");
+        assert_eq!(eval("substr(10)", "abc", 1), "");
+        assert_eq!(eval("pad(5, 0)", "42", 1), "00042");
+        assert_eq!(eval("pad(2)", "abc", 1), "abc");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("$# * 2 + 1", "", 5), "11");
+        assert_eq!(eval("($# - 1) / 2 % 3", "", 9), "1");
+        assert_eq!(eval("$_ * -2", "21 apples", 1), "-42");
+        assert_eq!(eval("$_ + 1", "none", 1), "1");
+        assert_eq!(eval("$#; pad(3, 0)", "", 7), "007");
+        assert_eq!(eval("$_ / 0", "1", 1), "0");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("").is_ok());
+        assert!(parse("s/a/b").is_err());
+        assert!(parse("s/(/b/").is_err());
+        assert!(parse("s/a/b/x").is_err());
+        assert!(parse("upper").is_err());
+        assert!(parse("substr(a)").is_err());
+        assert!(parse("$# +").is_err());
+        assert!(parse("uc lc").is_err());
+        assert_eq!(
+            parse("$x").unwrap_err(),
+            "unknown variable, $_ or $# expected at character 2 of the expression \"$x\""
+        );
+    }
+}
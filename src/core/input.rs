@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+
+/// Size of the blocks given to each job in `--pipe` mode when `--block` is not specified.
+pub const DEFAULT_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// The end of a record, blocks are only split after it.
+const RECORD_END: u8 = b'\n';
+
+/**
+ * Splits a stream into blocks of about `block_size` bytes, each block ending on a record boundary :
+ * - `reader : R` - the stream to split, usually the standard input
+ * - `block_size : usize` - the maximal size of a block (unless a single record is bigger)
+ * - `buffer : Vec<u8>` - data read from the stream that doesn't belong to a returned block yet
+ * - `eof : bool` - the stream has been fully read
+ *
+ * A block is made of the next `block_size` bytes, cut after the last complete record they contain.
+ * If a single record is bigger than `block_size` the block is extended until the end of this record.
+ * # Example
+ * ```rust
+ * use rust_parallel::core::input::BlockReader;
+ * let data: &[u8] = b"a\nbb\nccc\n";
+ * let blocks: Vec<Vec<u8>> = BlockReader::new(data, 5).collect();
+ * assert_eq!(blocks, vec![b"a\nbb\n".to_vec(), b"ccc\n".to_vec()]);
+ * ```
+ */
+pub struct BlockReader<R: Read> {
+    reader: R,
+    block_size: usize,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> BlockReader<R> {
+    /**
+     * Return a new block reader over the given stream.
+     * # Attributs
+     * - `reader: R` - the stream to split
+     * - `block_size: usize` - the wanted size of the blocks, 0 is considered as 1
+     */
+    pub fn new(reader: R, block_size: usize) -> BlockReader<R> {
+        BlockReader {
+            reader,
+            block_size: block_size.max(1),
+            buffer: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /**
+     * Private function.
+     *
+     * Read from the stream until at least `needed` bytes are buffered or the end of the stream is reached.
+     */
+    fn fill(&mut self, needed: usize) {
+        let mut chunk = vec![0; self.block_size.min(64 * 1024)];
+        while !self.eof && self.buffer.len() < needed {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                // a broken input is handled as its end, what has been read is still processed
+                Err(_) => self.eof = true,
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for BlockReader<R> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        self.fill(self.block_size);
+        if self.buffer.len() < self.block_size {
+            // the stream has been fully read, what remains is the last block
+            if self.buffer.is_empty() {
+                return None;
+            }
+            return Some(std::mem::take(&mut self.buffer));
+        }
+
+        // the block is cut after the last complete record it contains
+        let mut end = self.buffer[..self.block_size]
+            .iter()
+            .rposition(|c| *c == RECORD_END);
+
+        // otherwise the record is bigger than a block and is given entirely
+        let mut searched = self.block_size;
+        while end.is_none() {
+            match self.buffer[searched..]
+                .iter()
+                .position(|c| *c == RECORD_END)
+            {
+                Some(position) => end = Some(searched + position),
+                None if self.eof => return Some(std::mem::take(&mut self.buffer)),
+                None => {
+                    searched = self.buffer.len();
+                    self.fill(searched + self.block_size);
+                }
+            }
+        }
+
+        let rest = self.buffer.split_off(end.unwrap() + 1);
+        Some(std::mem::replace(&mut self.buffer, rest))
+    }
+}
+
+/**
+ * Return the values of a stream as they are read, one value per line without its line break.
+ * A line which isn't valid UTF-8 is returned as an `ErrorKind::InvalidData` error giving its number,
+ * so the caller decides whether it skips it, the next lines are still returned.
+ * # Arguments
+ * - `reader` - the stream of the values, ex: the standard input
+ */
+pub fn lines<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<String>> {
+    reader.split(RECORD_END).enumerate().map(|(index, line)| {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("the line {} isn't valid UTF-8", index + 1),
+            )
+        })
+    })
+}
+
+/**
+ * Read the values of an input source, one value per line.
+ * # Arguments
+ * - `path` - the file containing the values, "-" is the standard input
+ */
+pub fn read_values(path: &str) -> io::Result<Vec<String>> {
+    let reader: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    lines(BufReader::new(reader)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_reader_records() {
+        let data: &[u8] = b"1\n2\n3\n4\n5\n";
+        let blocks: Vec<Vec<u8>> = BlockReader::new(data, 4).collect();
+        assert_eq!(
+            blocks,
+            vec![b"1\n2\n".to_vec(), b"3\n4\n".to_vec(), b"5\n".to_vec()]
+        );
+    }
+
+    #[test]
+    fn block_reader_long_record() {
+        // a record bigger than the block is never split
+        let data: &[u8] = b"123456\n7\n89";
+        let blocks: Vec<Vec<u8>> = BlockReader::new(data, 2).collect();
+        assert_eq!(
+            blocks,
+            vec![b"123456\n".to_vec(), b"7\n".to_vec(), b"89".to_vec()]
+        );
+    }
+
+    #[test]
+    fn block_reader_empty() {
+        let data: &[u8] = b"";
+        assert_eq!(BlockReader::new(data, 10).next(), None);
+    }
+
+    #[test]
+    fn lines_invalid_utf8() {
+        // an invalid line is an error which doesn't end the input
+        let data: &[u8] = b"a\n\xff\r\nb\n\nc";
+        let values: Vec<io::Result<String>> = lines(data).collect();
+        assert_eq!(values.len(), 5);
+        let error = values[1].as_ref().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "the line 2 isn't valid UTF-8");
+        let valid: Vec<&str> = values.iter().flatten().map(String::as_str).collect();
+        assert_eq!(valid, vec!["a", "b", "", "c"]);
+    }
+
+    #[test]
+    fn read_values_file() {
+        assert!(read_values("Cargo.toml")
+            .unwrap()
+            .contains(&String::from("[package]")));
+        assert!(read_values("unknown_file.txt").is_err());
+    }
+}
@@ -2,20 +2,147 @@
 /// Disclaimer : this file works very closely with the grammar of rust-parallel,  ///
 /// if the grammar changes the code below should be maintained accordingly.       ///
 /////////////////////////////////////////////////////////////////////////////////////
-use super::job::Job;
-use super::jobmanager::JobManager;
+use super::diagnostic::Diagnostic;
+use super::expression::{self, Expression};
+use super::input::{self, read_values, BlockReader, DEFAULT_BLOCK_SIZE};
+use super::job::{shell_quote, Job, SLOT_MARK};
+use super::joblog::{self, JobLog};
+use super::jobmanager::{
+    nb_cpus, ExecOptions, HaltLimit, HaltPolicy, HaltWhen, JobManager, OptionsError, RetryPolicy,
+    Timeout, ERROR_EXIT_CODE, MAX_JOBS,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, ErrorKind, IsTerminal};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 // To see the avaible Rules & Pairs from the grammar:
-use super::parser::Rule;
+use super::parser::{self, ParseError, Rule};
 use pest::iterators::Pairs;
+use pest::Span;
 
 /// All interpretation errors that can be created by the complexity of
-/// parallel that the parser can not see. Returned by using the function
-/// interpret.
+/// parallel that the parser can not see, described with the words where
+/// they have been found when it is known. Returned by using the function
+/// interpret, the errors of the parser too with `interpret_args`.
+#[derive(Debug)]
 pub enum InterpretError {
     Help,
-    NoData(String),
-    BothSourceAndRemote(String),
+    Parse(ParseError),
+    NoData(Diagnostic),
+    BothSourceAndRemote(Diagnostic),
+    PipeWithValues(Diagnostic),
+    NoCommand(Diagnostic),
+    UnreadableFile(Diagnostic),
+    UnwritableFile(Diagnostic),
+    ResumeWithoutJoblog(Diagnostic),
+    InvalidExpression(Diagnostic),
+}
+
+impl InterpretError {
+    /// The exit code of the program for this error, it can't be confused
+    /// with a number of failed jobs.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            InterpretError::Help => 0,
+            _ => ERROR_EXIT_CODE,
+        }
+    }
+
+    /// The description of the error, the help has none.
+    pub fn diagnostic(&self) -> Option<&Diagnostic> {
+        match self {
+            InterpretError::Help => None,
+            InterpretError::Parse(error) => Some(error.diagnostic()),
+            InterpretError::NoData(diagnostic)
+            | InterpretError::BothSourceAndRemote(diagnostic)
+            | InterpretError::PipeWithValues(diagnostic)
+            | InterpretError::NoCommand(diagnostic)
+            | InterpretError::UnreadableFile(diagnostic)
+            | InterpretError::UnwritableFile(diagnostic)
+            | InterpretError::ResumeWithoutJoblog(diagnostic)
+            | InterpretError::InvalidExpression(diagnostic) => Some(diagnostic),
+        }
+    }
+
+    /// Changes the description of the error, the errors of the parser are already complete.
+    ///
+    /// ## PARAMS
+    /// - `change`: returns the new description from the current one
+    fn map(self, change: impl FnOnce(Diagnostic) -> Diagnostic) -> InterpretError {
+        match self {
+            InterpretError::NoData(diagnostic) => InterpretError::NoData(change(diagnostic)),
+            InterpretError::BothSourceAndRemote(diagnostic) => {
+                InterpretError::BothSourceAndRemote(change(diagnostic))
+            }
+            InterpretError::PipeWithValues(diagnostic) => {
+                InterpretError::PipeWithValues(change(diagnostic))
+            }
+            InterpretError::NoCommand(diagnostic) => InterpretError::NoCommand(change(diagnostic)),
+            InterpretError::UnreadableFile(diagnostic) => {
+                InterpretError::UnreadableFile(change(diagnostic))
+            }
+            InterpretError::UnwritableFile(diagnostic) => {
+                InterpretError::UnwritableFile(change(diagnostic))
+            }
+            InterpretError::ResumeWithoutJoblog(diagnostic) => {
+                InterpretError::ResumeWithoutJoblog(change(diagnostic))
+            }
+            InterpretError::InvalidExpression(diagnostic) => {
+                InterpretError::InvalidExpression(change(diagnostic))
+            }
+            error => error,
+        }
+    }
+
+    /// Moves the span of the words of the error, found in a part of the request.
+    ///
+    /// ## PARAMS
+    /// - `offset`: the offset of the part in the request
+    fn shifted(self, offset: usize) -> InterpretError {
+        self.map(|diagnostic| match diagnostic.span {
+            Some((start, end)) => diagnostic.at(start + offset, end + offset),
+            None => diagnostic,
+        })
+    }
+
+    /// Gives the span of the words of the error if it hasn't any yet.
+    ///
+    /// ## PARAMS
+    /// - `span`: the offending words in the request
+    fn at(self, span: &Span) -> InterpretError {
+        self.map(|diagnostic| match diagnostic.span {
+            Some(_) => diagnostic,
+            None => diagnostic.at(span.start(), span.end()),
+        })
+    }
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.diagnostic() {
+            Some(diagnostic) => write!(f, "{}", diagnostic),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Interprets the words of the command line (argv) and configures the job manager : they
+/// are checked and parsed (see `parser::request`) before being interpreted. The spans of
+/// the errors are in the line of the description.
+///
+/// ## PARAMS
+/// - `job_man`: the job manager receiving the jobs and the execution options
+/// - `args`: the words of the command line, without the program name
+pub fn interpret_args(job_man: &mut JobManager, args: &[String]) -> Result<(), InterpretError> {
+    let request = parser::request(args).map_err(InterpretError::Parse)?;
+    let mut pairs = parser::parse(&request).map_err(InterpretError::Parse)?;
+    interpret(job_man, &mut pairs)
+        .map_err(|error| error.map(|diagnostic| diagnostic.in_line(&request)))
 }
 
 /// Builds all the possible combinations according to sep_val values.
@@ -23,18 +150,19 @@ pub enum InterpretError {
 /// ## PARAMS
 /// - `builds`: the list containing all separators combinations
 /// - `sep_number`: current index of sep_val
-/// - `sep_val`: contains all the values of each separator
+/// - `sep_val`: contains all the values of each separator, the values of
+///   linked separators are given together (see `link_separators`)
 /// - `curr_build`: the current building combination of values
 fn build_combinations<'a>(
     builds: &mut Vec<Vec<&'a str>>,
     sep_number: usize,
-    sep_val: &Vec<Vec<&'a str>>,
+    sep_val: &'a [Vec<Vec<String>>],
     curr_build: Vec<&'a str>,
 ) {
     for i_value in 0..sep_val[sep_number].len() {
         let mut build = curr_build.clone();
-        let value = sep_val[sep_number][i_value];
-        build.push(value);
+        let values = sep_val[sep_number][i_value].iter();
+        build.extend(values.map(|value| value.as_str()));
         if sep_number == sep_val.len() - 1 {
             // we are at the smallest separator here
             //the iteration stops here (no recursive call)
@@ -46,15 +174,180 @@ fn build_combinations<'a>(
     }
 }
 
+/// Groups the values of the separators that are linked together, the
+/// combinations are then built between the groups only.
+///
+/// ## PARAMS
+/// - `separators`: contains all the values of each separator
+/// - `linked`: for each separator, true if it is linked to the previous one (:::+ or ::::+).
+///   The shortest of the linked separators gives the number of values.
+/// - `link_all`: all the separators are linked together (--link), the values
+///   of the shortest ones are recycled to match the longest one.
+fn link_separators(
+    separators: Vec<Vec<String>>,
+    linked: &[bool],
+    link_all: bool,
+) -> Vec<Vec<Vec<String>>> {
+    if link_all {
+        let length = separators.iter().map(|values| values.len()).max();
+        let linked_values = (0..length.unwrap_or(0))
+            .map(|i_value| {
+                separators
+                    .iter()
+                    .map(|values| values[i_value % values.len()].clone())
+                    .collect()
+            })
+            .collect();
+        return vec![linked_values];
+    }
+
+    let mut groups: Vec<Vec<Vec<String>>> = Vec::new();
+    for (separator, is_linked) in separators.into_iter().zip(linked) {
+        match groups.last_mut() {
+            Some(group) if *is_linked => {
+                group.truncate(separator.len());
+                for (linked_values, value) in group.iter_mut().zip(separator) {
+                    linked_values.push(value);
+                }
+            }
+            // the first separator can't be linked to a previous one
+            _ => groups.push(separator.into_iter().map(|value| vec![value]).collect()),
+        }
+    }
+    groups
+}
+
+/// Converts the value given to `--jobs` into a number of jobs, 0 means unlimited.
+///
+/// ## PARAMS
+/// - `jobs`: "N" for N jobs, "+N" or "-N" for N jobs more or less than the number
+///   of CPUs, "N%" for N percent of the number of CPUs (always at least one job),
+///   at most `MAX_JOBS` jobs
+fn parse_jobs(jobs: &str) -> usize {
+    let nb_cpus = nb_cpus();
+    // the digits never fail because the parse succeeded (except for an overflow).
+    let number = |digits: &str| digits.parse::<usize>().unwrap_or(usize::MAX);
+    let nb_jobs = if let Some(digits) = jobs.strip_prefix('+') {
+        nb_cpus.saturating_add(number(digits))
+    } else if let Some(digits) = jobs.strip_prefix('-') {
+        nb_cpus.saturating_sub(number(digits)).max(1)
+    } else if let Some(digits) = jobs.strip_suffix('%') {
+        (nb_cpus.saturating_mul(number(digits)) / 100).max(1)
+    } else {
+        number(jobs)
+    };
+    nb_jobs.min(MAX_JOBS)
+}
+
+/// Converts the value given to `--halt` into the policy stopping the execution.
+///
+/// ## PARAMS
+/// - `halt`: "never", or "now" or "soon" followed by the condition ("fail" or "success")
+///   and the number of jobs, ex: "now,fail=1" or "soon,success=20%"
+fn parse_halt(halt: &str) -> Option<HaltPolicy> {
+    let halt = halt.to_ascii_lowercase();
+    // the parse succeeded so the value is well formed (except for an overflow).
+    let (when, condition) = halt.split_once(',')?;
+    let (counted, limit) = condition.split_once('=')?;
+    let number = |digits: &str| digits.parse::<usize>().unwrap_or(usize::MAX);
+    Some(HaltPolicy {
+        when: if when == "now" {
+            HaltWhen::Now
+        } else {
+            HaltWhen::Soon
+        },
+        on_success: counted == "success",
+        limit: match limit.strip_suffix('%') {
+            Some(percent) => HaltLimit::Percent(number(percent)),
+            None => HaltLimit::Count(number(limit)),
+        },
+    })
+}
+
+/// Converts the value given to `--timeout` into the maximal runtime of the jobs.
+///
+/// ## PARAMS
+/// - `timeout`: a number of seconds, ex: "30" or "2.5", or a percentage of the median runtime
+///   of the completed jobs, ex: "200%"
+fn parse_timeout(timeout: &str) -> Timeout {
+    // the number never fails because the parse succeeded (except for an overflow).
+    match timeout.strip_suffix('%') {
+        Some(percent) => Timeout::Percent(percent.parse::<usize>().unwrap_or(usize::MAX)),
+        None => Timeout::Duration(parse_seconds(timeout)),
+    }
+}
+
+/// Converts a number of seconds, given to `--timeout` or `--retry-delay`, into a duration.
+///
+/// ## PARAMS
+/// - `seconds`: digits with an optional decimal part, ex: "30" or "2.5"
+fn parse_seconds(seconds: &str) -> Duration {
+    // the number never fails because the parse succeeded (except for an overflow).
+    Duration::try_from_secs_f64(seconds.parse::<f64>().unwrap_or(f64::MAX)).unwrap_or(Duration::MAX)
+}
+
+/// Converts a size given to `--block` into a number of bytes.
+///
+/// ## PARAMS
+/// - `size`: digits followed by an optional unit (k, m or g), ex: "10k"
+fn parse_size(size: &str) -> usize {
+    let unit_index = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let factor = match size[unit_index..].to_ascii_lowercase().as_str() {
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        _ => 1,
+    };
+    // the digits never fail because the parse succeeded (except for an overflow).
+    size[..unit_index]
+        .parse::<usize>()
+        .unwrap_or(usize::MAX)
+        .saturating_mul(factor)
+}
+
 pub fn interpret(job_man: &mut JobManager, inputs: &mut Pairs<Rule>) -> Result<(), InterpretError> {
-    let mut nb_thread: Option<usize> = None;
-    let mut dry_run: bool = false;
-    let mut keep_order: bool = false;
-    let mut src_port: Option<usize /*port number*/> = None; //server side
-    let mut dst_addr: Option<(String /*address*/, usize /*port number*/)> = None; //client side
+    // the execution options given to the job manager, --halt and the retries are given at the end
+    // as they are made of several options (the last --halt wins, "never" included)
+    let mut options = ExecOptions::new();
+    let mut pipe: bool = false;
+    let mut block_size: usize = DEFAULT_BLOCK_SIZE;
+    let mut server: bool = false;
+    let mut client: bool = false;
+    // the spans of the last --server and --client, reported if they are given together
+    let mut server_span: (usize, usize) = (0, 0);
+    let mut client_span: (usize, usize) = (0, 0);
+    // the span of the last --no-shell, reported if there is no command to execute
+    let mut no_shell_span: (usize, usize) = (0, 0);
 
-    let mut separators: Vec<Vec<&str>> = Vec::new();
+    // the values of each input source, given by ::: or read from the files of :::: and --arg-file
+    let mut separators: Vec<Vec<String>> = Vec::new();
+    // for each separator, true if it is linked to the previous one
+    let mut linked: Vec<bool> = Vec::new();
+    let mut link_all: bool = false;
+    let mut halt: Option<HaltPolicy> = None;
+    let mut retry = RetryPolicy {
+        retries: 0,
+        delay: Duration::from_secs(0),
+        backoff: false,
+    };
+    let mut joblog_path: Option<String> = None;
+    // --resume skips the jobs of the joblog, --resume-failed only the succeeded ones
+    // and --retry-failed executes again the failed ones only
+    let mut resume: bool = false;
+    let mut resume_failed: bool = false;
+    let mut retry_failed: bool = false;
     let mut command_pattern: String = String::from("");
+    // the offset of the command in the request, for the spans of its errors
+    let mut command_start: usize = 0;
+    // the values are quoted for the shell, --quote quotes the command too, --shell always
+    // executes it by the shell and --no-shell without any shell (the last one wins)
+    let mut quote: bool = false;
+    let mut use_shell: Option<bool> = None;
+    // the user-defined replacement strings by tag (without the braces), the ones
+    // of the command line replace the ones of the config file (read once --help is handled)
+    let mut rpl: BTreeMap<String, Expression> = BTreeMap::new();
 
     for pair in inputs
         .next()
@@ -63,50 +356,109 @@ pub fn interpret(job_man: &mut JobManager, inputs: &mut Pairs<Rule>) -> Result<(
     {
         match pair.as_rule() {
             Rule::options => {
-                let mut opt_iter = pair.as_str().split_whitespace();
+                // the values of the options can be quoted, ex: --joblog 'my jobs.log'
+                let span = pair.as_span();
+                let words = split_words(pair.as_str());
+                let mut opt_iter = words.iter().map(String::as_str);
                 match opt_iter.next().unwrap() {
-                    "--keep-order" => keep_order = true,
-                    "--dry-run" => dry_run = true,
+                    "--keep-order" => {
+                        options.keep_order(true);
+                    }
+                    "--dry-run" => {
+                        options.dry_run(true);
+                    }
                     // The parsed numbers (--jobs/-j/--server) and strings (--client) never fails because the parse succeeded.
                     "--jobs" | "-j" => {
-                        nb_thread = Some(opt_iter.next().unwrap().parse::<usize>().unwrap())
+                        options.jobs(parse_jobs(opt_iter.next().unwrap()));
                     }
                     "--client" => {
-                        dst_addr = Some((
-                            opt_iter.next().unwrap().parse::<String>().unwrap(),
+                        client = true;
+                        client_span = (span.start(), span.end());
+                        options.client(
+                            opt_iter.next().unwrap(),
                             opt_iter.next().unwrap().parse::<usize>().unwrap(),
-                        ))
+                        );
                     }
                     "--server" => {
-                        src_port = Some(opt_iter.next().unwrap().parse::<usize>().unwrap())
+                        options.server(opt_iter.next().unwrap().parse::<usize>().unwrap());
+                        server = true;
+                        server_span = (span.start(), span.end());
+                    }
+                    "--pipe" => pipe = true,
+                    "--block" => block_size = parse_size(opt_iter.next().unwrap()),
+                    "--arg-file" | "-a" => {
+                        let file = opt_iter.next().unwrap();
+                        separators.push(read_arg_file(file).map_err(|error| error.at(&span))?);
+                        linked.push(false);
                     }
-                    "--pipe" =>
-                    /*not yet implemented.*/
-                    {
-                        ()
+                    "--link" => link_all = true,
+                    "--halt" => halt = parse_halt(opt_iter.next().unwrap()),
+                    "--timeout" => {
+                        options.timeout(parse_timeout(opt_iter.next().unwrap()));
+                    }
+                    "--retries" => {
+                        retry.retries = opt_iter.next().unwrap().parse().unwrap_or(usize::MAX)
+                    }
+                    "--retry-delay" => retry.delay = parse_seconds(opt_iter.next().unwrap()),
+                    "--retry-backoff" => retry.backoff = true,
+                    "--joblog" => joblog_path = opt_iter.next().map(String::from),
+                    "--resume" => resume = true,
+                    "--resume-failed" => resume_failed = true,
+                    "--retry-failed" => retry_failed = true,
+                    "--quote" | "-q" => quote = true,
+                    "--shell" => use_shell = Some(true),
+                    "--no-shell" => {
+                        use_shell = Some(false);
+                        no_shell_span = (span.start(), span.end());
+                    }
+                    "--rpl" => {
+                        // a tag and its expression, ex: '{..} s/\.[^.]+\.[^.]+$//'
+                        let (tag, expression) =
+                            parse_rpl(opt_iter.next().unwrap()).map_err(|error| error.at(&span))?;
+                        rpl.insert(tag, expression);
                     }
                     "--help" => return Err(InterpretError::Help),
                     _ => unreachable!(),
                 }
             }
-            Rule::commands => command_pattern = String::from(pair.into_inner().as_str()),
+            Rule::commands => {
+                command_start = pair.as_span().start();
+                command_pattern = String::from(pair.into_inner().as_str())
+            }
             Rule::separators => {
-                let mut separator = Vec::new();
-                for sep_values in pair.into_inner().skip(1)
+                let span = pair.as_span();
+                let mut inner = pair.into_inner();
+                let mut separator: Vec<String> = Vec::new();
+                for sep_values in inner.clone().skip(1)
                 /*we skip the "separator" rule*/
                 {
-                    // here we are on "input" rule
-                    match sep_values.as_rule() {
-                        Rule::string => separator.push(sep_values.as_str()),
-                        _ /*some digits*/ => {
-                            for sep_value in sep_values.as_str().split_whitespace() {
-                                separator.push(sep_value);
-                            }
+                    // here we are on "input" rule, a value which can be quoted
+                    separator.extend(split_words(sep_values.as_str()));
+                }
+
+                let separator_rule = inner.next().unwrap();
+                // ":::+" and "::::+" link the values to the previous separator
+                let is_linked = separator_rule.as_str().ends_with('+');
+                match separator_rule.as_rule() {
+                    // with "::::" the inputs are files, each one is an input source
+                    Rule::file_separator => {
+                        if separator.is_empty() {
+                            // so the missing files are reported below
+                            separators.push(Vec::new());
+                            linked.push(is_linked);
                         }
+                        for file in separator {
+                            separators.push(read_arg_file(&file).map_err(|error| error.at(&span))?);
+                            linked.push(is_linked);
+                        }
+                    }
+                    _ => {
+                        separators.push(separator);
+                        linked.push(is_linked);
                     }
                 }
-                separators.push(separator);
             }
+            Rule::EOI => (),
             // some rules are not reachable from main rule,
             // that is totaly normal according to the grammar.
             // this part of the code is unreachable.
@@ -114,111 +466,807 @@ pub fn interpret(job_man: &mut JobManager, inputs: &mut Pairs<Rule>) -> Result<(
         }
     }
 
-    if src_port.is_some() && dst_addr.is_some() {
-        return Err(InterpretError::BothSourceAndRemote(String::from(
-            "You can't be the server and the client at the same time.",
-        )));
+    if let Some(path) = config_path() {
+        for (tag, expression) in read_config(&path) {
+            rpl.entry(tag).or_insert(expression);
+        }
+    }
+
+    let mode = match use_shell {
+        Some(false) => ExecMode::NoShell,
+        _ if quote => ExecMode::QuotedShell,
+        Some(true) => ExecMode::Shell,
+        None => ExecMode::Auto,
+    };
+
+    if let Some(halt) = halt {
+        options.halt(halt);
     }
+    if retry.retries > 0 {
+        options.retries(retry);
+    }
+    // the options are checked before anything is read or written
+    job_man.set_options(&options).map_err(|error| match error {
+        OptionsError::ServerAndClient => InterpretError::BothSourceAndRemote(
+            Diagnostic::new(error.to_string())
+                .at(client_span.0, client_span.1)
+                .also_at(server_span.0, server_span.1),
+        ),
+    })?;
+
+    // the standard input of a client isn't sent to its server, and the one of a server isn't
+    // the one of its clients, so they can't read values from it
+    let stdin_error = if client {
+        Some(
+            Diagnostic::new(String::from(
+                "The standard input isn't sent to the server (--client), give the values with ::: or ::::",
+            ))
+            .at(client_span.0, client_span.1),
+        )
+    } else if !job_man.stdin() {
+        Some(Diagnostic::new(String::from(
+            "The server can't read the standard input, give the values with ::: or ::::",
+        )))
+    } else {
+        None
+    };
 
-    // TODO : add pipe input in separators values.
+    // the joblog of the previous execution is read before the new jobs are added to it
+    let mut previous_jobs = BTreeMap::new();
+    let joblog = match joblog_path.as_deref() {
+        Some(path) if resume || resume_failed || retry_failed => {
+            previous_jobs = joblog::read(path).map_err(|error| {
+                InterpretError::UnreadableFile(Diagnostic::new(format!(
+                    "Can't read the joblog {} : {}",
+                    path, error
+                )))
+            })?;
+            Some(open_joblog(JobLog::append(path), path)?)
+        }
+        Some(path) => Some(open_joblog(JobLog::create(path), path)?),
+        None if resume || resume_failed || retry_failed => {
+            return Err(InterpretError::ResumeWithoutJoblog(Diagnostic::new(
+                String::from("You must give the joblog of the execution to resume with --joblog"),
+            )));
+        }
+        None => None,
+    };
+    // the jobs are identified by their sequence number, given by their order,
+    // the killed ones didn't complete so they are executed again
+    let skip = previous_jobs
+        .values()
+        .filter(|job| (resume && !job.killed()) || job.succeeded())
+        .map(|job| job.seq)
+        .collect::<BTreeSet<usize>>();
+
+    if retry_failed {
+        // the commands are the ones of the joblog, the command line is ignored
+        let last_seq = previous_jobs.keys().next_back().copied().unwrap_or(0);
+        for seq in 1..=last_seq {
+            match previous_jobs.get(&seq) {
+                Some(job) => job_man.add_job(shell_job(&job_man.shell, &job.command)),
+                // a job missing from the joblog isn't executed, it only keeps its sequence number
+                None => job_man.add_job(shell_job(&job_man.shell, "")),
+            }
+        }
+        let executed: BTreeSet<usize> = previous_jobs.keys().copied().collect();
+        job_man.set_skip(
+            (1..=last_seq)
+                .filter(|seq| skip.contains(seq) || !executed.contains(seq))
+                .collect(),
+        );
+    } else if pipe {
+        if !separators.is_empty() {
+            return Err(InterpretError::PipeWithValues(Diagnostic::new(
+                String::from(
+                    "You can't give values with ::: when the input is piped to the jobs (--pipe)",
+                ),
+            )));
+        }
+
+        if let Some(diagnostic) = stdin_error {
+            return Err(InterpretError::NoData(diagnostic));
+        }
+
+        // without any value, the command gives the program executed without a shell
+        if mode == ExecMode::NoShell && split_words(&command_pattern).is_empty() {
+            return Err(InterpretError::NoCommand(
+                Diagnostic::new(String::from(
+                    "You must give the command to execute without a shell (--no-shell) when the input is piped to the jobs (--pipe)",
+                ))
+                .at(no_shell_span.0, no_shell_span.1),
+            ));
+        }
+
+        // Each block of the standard input is given to a new job running the command
+        let command_pattern = CommandPattern::parse(&command_pattern, &rpl, mode)
+            .map_err(|error| error.shifted(command_start))?;
+        let shell = job_man.shell.clone();
+        let blocks = BlockReader::new(io::stdin(), block_size);
+        job_man.set_source(Box::new(blocks.enumerate().map(move |(seq, block)| {
+            let mut job = command_pattern.job(&shell, &[], seq + 1);
+            job.set_stdin(block);
+            job
+        })));
+    } else if separators.iter().any(|separator| separator.is_empty()) {
+        return Err(InterpretError::NoData(Diagnostic::new(String::from(
+            "You forgot to give values after ::: or files after ::::",
+        ))));
+    } else if !separators.is_empty() {
+        // the linked separators give their values together
+        let groups = link_separators(separators, &linked, link_all);
 
-    if separators.len() > 0 {
         // a vector that will contain all possible combinations
         let mut combinations: Vec<Vec<&str>> = Vec::new();
         // build all possible combinations from separators values
-        build_combinations(&mut combinations, 0, &separators, Vec::new());
+        build_combinations(&mut combinations, 0, &groups, Vec::new());
 
         // Create all jobs here from the command's pattern
-        create_all_jobs(job_man, &combinations, command_pattern);
-    } else {
-        if src_port.is_none() {
-            return Err(InterpretError::NoData(String::from(
+        let command_pattern = CommandPattern::parse(&command_pattern, &rpl, mode)
+            .map_err(|error| error.shifted(command_start))?;
+        create_all_jobs(job_man, &combinations, &command_pattern);
+    } else if !server {
+        if let Some(diagnostic) = stdin_error {
+            return Err(InterpretError::NoData(diagnostic));
+        }
+        if io::stdin().is_terminal() {
+            return Err(InterpretError::NoData(Diagnostic::new(String::from(
                 "You forgot ::: or to pipe data into parallel",
-            )));
+            ))));
         }
+
+        // Each line of the standard input is a value, the jobs are created while
+        // the lines arrive so the first ones can start before the input is closed
+        let command_pattern = CommandPattern::parse(&command_pattern, &rpl, mode)
+            .map_err(|error| error.shifted(command_start))?;
+        let shell = job_man.shell.clone();
+        // a line which isn't valid UTF-8 is skipped, a read error ends the input,
+        // the jobs of the lines read before are executed
+        let lines = input::lines(BufReader::new(io::stdin()))
+            .map_while(|line| match line {
+                Ok(line) => Some(Some(line)),
+                Err(error) if error.kind() == ErrorKind::InvalidData => {
+                    eprintln!("Warning : {} in the standard input, it is skipped", error);
+                    Some(None)
+                }
+                Err(error) => {
+                    eprintln!("Warning : can't read the standard input : {}", error);
+                    None
+                }
+            })
+            .flatten();
+        job_man.set_source(Box::new(lines.enumerate().map(move |(seq, line)| {
+            command_pattern.job(&shell, &[line.as_str()], seq + 1)
+        })));
     }
 
-    job_man.set_exec_env(nb_thread, dry_run, keep_order, src_port, dst_addr);
+    job_man.set_joblog(joblog);
+    if !retry_failed {
+        job_man.set_skip(skip);
+    }
     Ok(())
 }
 
-fn create_job(job_man: &mut JobManager, command: &str) {
+/// Reads the values of an input source from a file, one value per line.
+/// The file "-" is the standard input.
+fn read_arg_file(path: &str) -> Result<Vec<String>, InterpretError> {
+    read_values(path).map_err(|error| {
+        InterpretError::UnreadableFile(Diagnostic::new(format!(
+            "Can't read the file {} : {}",
+            path, error
+        )))
+    })
+}
+
+/// Reports the joblog recording the executed jobs which can't be opened.
+fn open_joblog(joblog: io::Result<JobLog>, path: &str) -> Result<JobLog, InterpretError> {
+    joblog.map_err(|error| {
+        InterpretError::UnwritableFile(Diagnostic::new(format!(
+            "Can't write the joblog {} : {}",
+            path, error
+        )))
+    })
+}
+
+/// Returns the config file of parallel : `$PARALLEL_HOME/config`, or `~/.parallel/config`
+/// without PARALLEL_HOME.
+#[cfg(not(test))]
+fn config_path() -> Option<PathBuf> {
+    match env::var_os("PARALLEL_HOME") {
+        Some(home) => Some(PathBuf::from(home).join("config")),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".parallel/config")),
+    }
+}
+
+/// The tests never read the config file of the user.
+#[cfg(test)]
+fn config_path() -> Option<PathBuf> {
+    None
+}
+
+/// Reads the user-defined replacement strings of the config file, one `--rpl 'TAG EXPRESSION'`
+/// per line like in GNU parallel. The empty lines, the comments (#) and the other options are
+/// ignored, a missing config file defines nothing.
+/// The file is shared with GNU parallel, so a definition which can't be used here (ex: a Perl
+/// expression) or an unreadable file is only reported by a warning and the execution goes on.
+fn read_config(path: &Path) -> BTreeMap<String, Expression> {
+    let mut rpl = BTreeMap::new();
+    let config = match fs::read_to_string(path) {
+        Ok(config) => config,
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return rpl,
+        Err(error) => {
+            eprintln!(
+                "Warning : can't read the config file {} : {}",
+                path.display(),
+                error
+            );
+            return rpl;
+        }
+    };
+
+    for line in config.lines().map(str::trim) {
+        if let Some(definition) = line.strip_prefix("--rpl") {
+            let definition = definition.trim();
+            // the definition is usually quoted as on the command line
+            let definition = ["'", "\""]
+                .iter()
+                .find_map(|quote| definition.strip_prefix(quote)?.strip_suffix(quote))
+                .unwrap_or(definition);
+            match parse_rpl(definition) {
+                Ok((tag, expression)) => {
+                    rpl.insert(tag, expression);
+                }
+                Err(error) => eprintln!(
+                    "Warning : --rpl '{}' of the config file {} is ignored : {}",
+                    definition,
+                    path.display(),
+                    error
+                        .diagnostic()
+                        .map_or("", |diagnostic| &diagnostic.message)
+                ),
+            }
+        }
+    }
+    rpl
+}
+
+/// Converts the definition of a user-defined replacement string (`--rpl`) into its tag,
+/// without the braces, and its expression.
+///
+/// ## PARAMS
+/// - `definition`: the tag followed by the expression, ex: "{..} s/\\.[^.]+\\.[^.]+$//"
+fn parse_rpl(definition: &str) -> Result<(String, Expression), InterpretError> {
+    let invalid = || {
+        InterpretError::InvalidExpression(Diagnostic::new(format!(
+            "the replacement string \"{}\" should be a tag like {{..}} followed by an expression",
+            definition
+        )))
+    };
+    let (tag, source) = definition
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let name = tag
+        .strip_prefix('{')
+        .and_then(|tag| tag.strip_suffix('}'))
+        .ok_or_else(invalid)?;
+    // the digits and "=" at the start of a tag are kept for {2.} and {= =}
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit() || c == '=')
+        || name.contains(|c: char| c == '{' || c == '}' || c.is_whitespace())
+    {
+        return Err(invalid());
+    }
+    let expression = expression::parse(source)
+        .map_err(|reason| InterpretError::InvalidExpression(Diagnostic::new(reason)))?;
+    Ok((String::from(name), expression))
+}
+
+fn shell_job(shell: &str, command: &str) -> Job {
     // the job will be executed in the given shell from the job_manager.
-    let mut new_shell_job = vec![String::from(job_man.shell.as_str())];
+    let mut new_shell_job = vec![String::from(shell)];
     new_shell_job.push(String::from("-c"));
     new_shell_job.push(String::from(command));
-    job_man.add_job(Job::new(new_shell_job));
+    Job::new(new_shell_job)
 }
 
 fn create_all_jobs(
     job_man: &mut JobManager,
-    combinations: &Vec<Vec<&str>>,
-    command_pattern: String,
+    combinations: &[Vec<&str>],
+    command_pattern: &CommandPattern,
 ) {
-    for combination in combinations {
-        // we un-quote special characters.
-        let mut command = command_pattern.replace("'", "");
-
-        // in parallel, having no targets or a "{}" target while having
-        // one or multiple seprators has the same behaviour has "{1}" for
-        // one separator, "{1} {2}" for two separators, "{1} {2} {3}" for
-        // three separators, etc.
-        let mut combo = String::from("");
-        for value in combination {
-            combo.push_str(value);
-            combo.push(' ');
-        }
-        combo = combo.trim_end().to_string();
-
-        // we check if actual targets exist
-        let mut target_exists = false;
-        let open_braces = command.find('{').unwrap_or(0);
-        let close_braces = command.find('}').unwrap_or(0);
-        if open_braces < close_braces {
-            // braces exists in a good order, but the
-            // content will be checked after in the while loop
-            target_exists = true;
-        }
-
-        if !target_exists {
+    for (index, combination) in combinations.iter().enumerate() {
+        // the sequence number of a job is its place in the combinations, from 1
+        let job = command_pattern.job(&job_man.shell, combination, index + 1);
+        job_man.add_job(job);
+    }
+}
+
+/// The characters interpreted by the shell, a command containing one of them is executed by the shell.
+/// The quotes are handled like the shell does, they don't need it.
+const SHELL_METACHARACTERS: &str = "|&;<>()$`*?[]{}#~=!\n";
+
+/// How the command of a job is executed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExecMode {
+    /// without any shell if the command doesn't need it, by the shell otherwise
+    Auto,
+    /// by the shell, the values are quoted so the shell gives them as they are to the command
+    Shell,
+    /// by the shell, the words of the command are quoted too (--quote)
+    QuotedShell,
+    /// without any shell, the words of the command are its arguments (--no-shell)
+    NoShell,
+}
+
+/// The command's pattern split into its text and its targets,
+/// so its expressions are parsed only once for all the jobs.
+struct CommandPattern {
+    parts: Vec<Part>,
+    target_exists: bool,
+    mode: ExecMode,
+}
+
+/// A part of the command's pattern.
+enum Part {
+    Text(String),
+    /// {#}
+    Seq,
+    /// {%}
+    Slot,
+    /// what is between the braces, ex: "" for "{}" or "2/." for "{2/.}"
+    Target(String),
+    /// the position of the value given to the expression ("" for all the values) and the expression
+    Expression(String, Expression),
+}
+
+impl CommandPattern {
+    /// Splits the command's pattern into its parts.
+    ///
+    /// ## PARAMS
+    /// - `command_pattern`: the command with its targets, ex: "convert {} {.}.png" or "echo {= uc =}"
+    /// - `rpl`: the user-defined replacement strings by tag, they can be positional like {2..}
+    /// - `mode`: how the commands built from the pattern are executed
+    fn parse(
+        command_pattern: &str,
+        rpl: &BTreeMap<String, Expression>,
+        mode: ExecMode,
+    ) -> Result<CommandPattern, InterpretError> {
+        // the quotes are kept, the command is un-quoted by the shell or when it is split into words
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = command_pattern;
+        while let Some(open_braces) = rest.find('{') {
+            text.push_str(&rest[..open_braces]);
+            rest = &rest[open_braces..];
+
+            if let Some(source) = rest.strip_prefix("{=") {
+                // an expression ends at the first "=}", it can contain braces
+                if let Some(end) = source.find("=}") {
+                    let digits_end = source
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(source.len());
+                    // the span of the expression in the command's pattern, with its braces
+                    let start = command_pattern.len() - rest.len();
+                    let expression =
+                        expression::parse(&source[digits_end..end]).map_err(|reason| {
+                            InterpretError::InvalidExpression(
+                                Diagnostic::new(reason).at(start, start + end + 4),
+                            )
+                        })?;
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Expression(
+                        String::from(&source[..digits_end]),
+                        expression,
+                    ));
+                    rest = &source[end + 2..];
+                    continue;
+                }
+            }
+
+            // the braces which aren't targets (ex: "{a,b}") are kept as they are
+            // and the user-defined replacement strings replace the predefined ones
+            match rest.find('}').map(|close_braces| &rest[1..close_braces]) {
+                Some(target) if rpl.contains_key(split_position(target).1) => {
+                    let (position, name) = split_position(target);
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Expression(String::from(position), rpl[name].clone()));
+                    rest = &rest[target.len() + 2..];
+                }
+                Some(target) if is_target(target) => {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(match target {
+                        "#" => Part::Seq,
+                        "%" => Part::Slot,
+                        _ => Part::Target(String::from(target)),
+                    });
+                    rest = &rest[target.len() + 2..];
+                }
+                _ => {
+                    text.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        parts.push(Part::Text(text));
+
+        let target_exists = parts.iter().any(|part| !matches!(part, Part::Text(_)));
+        let mut command_pattern = CommandPattern {
+            parts,
+            target_exists,
+            mode,
+        };
+        if mode == ExecMode::Auto {
+            // a shell per job is only needed to interpret the command
+            command_pattern.mode = if command_pattern.needs_shell() {
+                ExecMode::Shell
+            } else {
+                ExecMode::NoShell
+            };
+        }
+        Ok(command_pattern)
+    }
+
+    /// Returns true if the command must be executed by the shell : its text contains characters
+    /// interpreted by the shell, or its program isn't an executable file (ex: a builtin like cd,
+    /// a keyword like for or the values themselves without any command).
+    /// The values don't matter, they are never interpreted.
+    fn needs_shell(&self) -> bool {
+        let metacharacters = self.parts.iter().any(|part| match part {
+            Part::Text(text) => text.contains(|c| SHELL_METACHARACTERS.contains(c)),
+            _ => false,
+        });
+        // the program is the first word, it must not be followed by a target (ex: "cmd{}")
+        let mut words = WordSplitter::default();
+        if let Some(Part::Text(text)) = self.parts.first() {
+            words.push_text(text);
+        }
+        let program = match words.words.first() {
+            Some(program) => program.clone(),
+            None if !self.target_exists => words.finish().pop().unwrap_or_default(),
+            None => String::new(),
+        };
+        metacharacters || !is_executable(&program)
+    }
+
+    /// Returns the job executing the command built for a combination of values.
+    ///
+    /// ## PARAMS
+    /// - `shell`: the shell executing the command, unless the mode is `NoShell`
+    /// - `combination`: the values of the job, one per input source
+    /// - `seq`: the sequence number of the job, from 1 ({#})
+    fn job(&self, shell: &str, combination: &[&str], seq: usize) -> Job {
+        match self.mode {
+            ExecMode::NoShell => Job::new(self.build_args(combination, seq)),
+            _ => shell_job(shell, &self.build(combination, seq)),
+        }
+    }
+
+    /// Builds the command of a job, executed by the shell, by replacing the targets
+    /// of the command's pattern with the quoted values of the combination.
+    /// Without any target, the values are appended to the command.
+    ///
+    /// ## PARAMS
+    /// - `combination`: the values of the job, one per input source
+    /// - `seq`: the sequence number of the job, from 1 ({#})
+    fn build(&self, combination: &[&str], seq: usize) -> String {
+        if self.mode == ExecMode::QuotedShell {
+            // the words of the command are given as they are to the program too
+            let args = self.build_args(combination, seq);
+            return quote_values(&args);
+        }
+
+        let mut command = String::new();
+        // the values are quoted according to the quotes of the command around them
+        let mut words = WordSplitter::default();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => {
+                    words.push_text(text);
+                    command.push_str(text);
+                }
+                Part::Seq => command.push_str(&seq.to_string()),
+                // the job slot is only known once the job starts
+                Part::Slot => command.push_str(SLOT_MARK),
+                _ => {
+                    let values = part.values(combination, seq);
+                    command.push_str(&match words.quote {
+                        Quote::Unquoted => quote_values(&values),
+                        Quote::Single => values.join(" ").replace('\'', "'\\''"),
+                        Quote::Double => escape_double_quoted(&values.join(" ")),
+                    });
+                }
+            }
+        }
+
+        // in parallel, having no targets while having one or multiple separators
+        // has the same behaviour as "{}", the values are given separated by spaces.
+        if !self.target_exists && !combination.is_empty() {
             command.push(' ');
-            command.push_str(combo.as_str());
-        } else {
-            command = command.replace("{}", combo.as_str());
-            // If we encounter special targets like "{1}" we replace them with
-            // the right value of the combination.
-            while target_exists {
-                let open_braces = command.find('{').unwrap_or(0);
-                let close_braces = command.find('}').unwrap_or(0);
-                if open_braces < close_braces {
-                    let braces_content = command[open_braces + 1..close_braces].parse::<usize>();
-                    match braces_content {
-                        Ok(value) => {
-                            if value <= combination.len() {
-                                let index = if value > 0 {
-                                    value - 1
-                                } else {
-                                    0 /*{0} is considered as {1} in parallel*/
-                                };
-                                command
-                                    .replace_range(open_braces..=close_braces, combination[index]);
-                            } else {
-                                // The value is above the separator's index
-                                // ex : specifying target {3} while only two dimensions were specified.
-                                // So we ignore the target and erase it as parallel would do the same.
-                                command.replace_range(open_braces..=close_braces, "");
-                            }
-                        }
-                        // an error occured while parsing the usize
-                        _ => target_exists = false,
-                    }
+            if command.trim().is_empty() {
+                // without any command, the values are the commands
+                command.push_str(&combination.join(" "));
+            } else {
+                command.push_str(&quote_values(combination));
+            }
+        }
+        command
+    }
+
+    /// Builds the arguments of a job executed without any shell, the first one is the program.
+    /// The words of the command's pattern are split and un-quoted like the shell does, and the
+    /// values are never split. Like with the shell, the values of a target (or appended without
+    /// any target) are separate arguments, ex: "x{}" with the values "a b" and "c" gives "xa b"
+    /// and "c", unless the target is quoted.
+    ///
+    /// ## PARAMS
+    /// - `combination`: the values of the job, one per input source
+    /// - `seq`: the sequence number of the job, from 1 ({#})
+    fn build_args(&self, combination: &[&str], seq: usize) -> Vec<String> {
+        let mut words = WordSplitter::default();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => words.push_text(text),
+                Part::Seq => words.push_values(&[seq.to_string()]),
+                Part::Slot => words.push_values(&[SLOT_MARK]),
+                _ => words.push_values(&part.values(combination, seq)),
+            }
+        }
+        let mut args = words.finish();
+
+        if !self.target_exists {
+            args.extend(combination.iter().map(|value| value.to_string()));
+        }
+        args
+    }
+}
+
+impl Part {
+    /// Returns the values given by a target or an expression for a job, transformed.
+    ///
+    /// ## PARAMS
+    /// - `combination`: the values of the job, one per input source
+    /// - `seq`: the sequence number of the job ({#}), used by the expressions
+    fn values(&self, combination: &[&str], seq: usize) -> Vec<String> {
+        match self {
+            Part::Target(target) => {
+                let (position, suffix) = split_position(target);
+                let transform = transform(suffix).unwrap_or(|value| String::from(value));
+                positional_values(position, combination)
+                    .into_iter()
+                    .map(transform)
+                    .collect()
+            }
+            // like the other targets, each value is transformed
+            Part::Expression(position, expression) => positional_values(position, combination)
+                .into_iter()
+                .map(|value| expression.eval(value, seq))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Returns true if the program is an executable file, given by its path or found in the PATH.
+fn is_executable(program: &str) -> bool {
+    let executable = |path: PathBuf| {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+    if program.is_empty() {
+        false
+    } else if program.contains('/') {
+        executable(PathBuf::from(program))
+    } else {
+        env::var_os("PATH")
+            .is_some_and(|paths| env::split_paths(&paths).any(|dir| executable(dir.join(program))))
+    }
+}
+
+/// Returns the values quoted for the shell, separated by spaces.
+fn quote_values<S: AsRef<str>>(values: &[S]) -> String {
+    values
+        .iter()
+        .map(|value| shell_quote(value.as_ref()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Returns the value escaped to be in double quotes, the characters still interpreted
+/// by the shell in double quotes are preceded by a backslash.
+fn escape_double_quoted(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if "$`\"\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The quotes around a character of a command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Quote {
+    Unquoted,
+    /// 'a b', nothing is interpreted
+    Single,
+    /// "a b", only $, ` and the backslashes are interpreted
+    Double,
+}
+
+/// Splits a command into words and un-quotes them like a POSIX shell : the words are separated by
+/// the unquoted whitespaces, a backslash escapes the next character (in double quotes, only $, `, "
+/// a backslash and a newline) and nothing is escaped in single quotes.
+/// The values given between the parts of the command are kept as they are.
+struct WordSplitter {
+    /// the complete words
+    words: Vec<String>,
+    /// the current word, an empty quoted word ('') is still a word
+    word: Option<String>,
+    quote: Quote,
+    /// the previous character is a backslash
+    escaped: bool,
+}
+
+impl Default for WordSplitter {
+    fn default() -> WordSplitter {
+        WordSplitter {
+            words: Vec::new(),
+            word: None,
+            quote: Quote::Unquoted,
+            escaped: false,
+        }
+    }
+}
+
+impl WordSplitter {
+    /// Adds a part of the command, written as in the shell.
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.escaped {
+                self.escaped = false;
+                if self.quote == Quote::Double && !"$`\"\\\n".contains(c) {
+                    // the backslash is kept if it doesn't escape anything
+                    self.push('\\');
+                }
+                // an escaped end of line continues the line
+                if c != '\n' {
+                    self.push(c);
+                }
+                continue;
+            }
+            match (self.quote, c) {
+                (Quote::Single, '\'') | (Quote::Double, '"') => self.quote = Quote::Unquoted,
+                (Quote::Unquoted, '\'') => self.open(Quote::Single),
+                (Quote::Unquoted, '"') => self.open(Quote::Double),
+                (Quote::Unquoted, '\\') | (Quote::Double, '\\') => {
+                    self.escaped = true;
+                    self.word.get_or_insert_with(String::new);
+                }
+                (Quote::Unquoted, c) if c.is_whitespace() => self.words.extend(self.word.take()),
+                (_, c) => self.push(c),
+            }
+        }
+    }
+
+    /// Adds values between the parts of the command, each one is a word
+    /// unless they are quoted (ex: "{}"), then they are separated by spaces.
+    fn push_values<S: AsRef<str>>(&mut self, values: &[S]) {
+        for (index, value) in values.iter().enumerate() {
+            if index > 0 {
+                if self.quote == Quote::Unquoted {
+                    self.words.extend(self.word.take());
                 } else {
-                    // no valid braces found !
-                    target_exists = false;
+                    self.push(' ');
                 }
             }
+            self.word
+                .get_or_insert_with(String::new)
+                .push_str(value.as_ref());
+        }
+    }
+
+    /// Returns the words of the command, a final backslash is kept.
+    fn finish(mut self) -> Vec<String> {
+        if self.escaped {
+            self.push('\\');
         }
-        create_job(job_man, &command);
+        self.words.extend(self.word);
+        self.words
+    }
+
+    fn push(&mut self, c: char) {
+        self.word.get_or_insert_with(String::new).push(c);
+    }
+
+    fn open(&mut self, quote: Quote) {
+        self.quote = quote;
+        self.word.get_or_insert_with(String::new);
+    }
+}
+
+/// Returns the words of a text written as in the shell, un-quoted, ex: "a 'b c'" gives "a" and "b c".
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = WordSplitter::default();
+    words.push_text(text);
+    words.finish()
+}
+
+/// Returns the function transforming the values for the end of a target
+/// (ex: "." for "{.}" or "{2.}"), None if it isn't a target.
+fn transform(suffix: &str) -> Option<fn(&str) -> String> {
+    let transform: fn(&str) -> String = match suffix {
+        "" => |value| String::from(value),
+        "." => |value| String::from(remove_extension(value)),
+        "/" => |value| String::from(basename(value)),
+        "//" => |value| String::from(dirname(value)),
+        "/." => |value| String::from(remove_extension(basename(value))),
+        _ => return None,
+    };
+    Some(transform)
+}
+
+/// Splits what is between the braces of a target into its position and the end of the target,
+/// ex: "2/." gives ("2", "/.").
+fn split_position(target: &str) -> (&str, &str) {
+    let digits_end = target
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(target.len());
+    target.split_at(digits_end)
+}
+
+/// Returns true if what is between braces is a target, ex: "", "2", "." or "#".
+fn is_target(target: &str) -> bool {
+    target == "#" || target == "%" || transform(split_position(target).1).is_some()
+}
+
+/// Returns the values of the combination at the position of a target.
+///
+/// ## PARAMS
+/// - `position`: the digits of the target, all the values if it is empty
+/// - `combination`: the values of the job, one per input source
+fn positional_values<'a>(position: &str, combination: &[&'a str]) -> Vec<&'a str> {
+    if position.is_empty() {
+        // without a position, the target gives all the values of the combination
+        return combination.to_vec();
+    }
+    // {0} is considered as {1} in parallel (the digits only fail for an overflow).
+    let index = position.parse::<usize>().unwrap_or(usize::MAX);
+    // The value is above the separator's index
+    // ex : specifying target {3} while only two dimensions were specified.
+    // So the target is erased as parallel would do the same.
+    combination
+        .get(index.saturating_sub(1))
+        .into_iter()
+        .copied()
+        .collect()
+}
+
+/// Returns the value without its extension ({.}), the extension starts at the last dot of the file name.
+fn remove_extension(value: &str) -> &str {
+    match value.rfind('.') {
+        Some(dot) if dot > 0 && !value[dot..].contains('/') && !value[..dot].ends_with('/') => {
+            &value[..dot]
+        }
+        _ => value,
+    }
+}
+
+/// Returns the file name of the value, without its directory ({/}).
+fn basename(value: &str) -> &str {
+    match value.rfind('/') {
+        Some(slash) => &value[slash + 1..],
+        None => value,
+    }
+}
+
+/// Returns the directory of the value ({//}), "." if it has none.
+fn dirname(value: &str) -> &str {
+    match value.rfind('/') {
+        Some(0) => "/",
+        Some(slash) => &value[..slash],
+        None => ".",
     }
 }
 
@@ -226,6 +1274,13 @@ fn create_all_jobs(
 mod tests {
     use super::*;
 
+    fn build_command(command_pattern: &str, combination: &[&str], seq: usize) -> String {
+        match CommandPattern::parse(command_pattern, &BTreeMap::new(), ExecMode::Shell) {
+            Ok(command_pattern) => command_pattern.build(combination, seq),
+            Err(_) => panic!("invalid command pattern {}", command_pattern),
+        }
+    }
+
     #[test]
     fn builder_test1() {
         let mut jm = JobManager::new(String::from("/bin/bash"));
@@ -298,5 +1353,612 @@ mod tests {
             Err(InterpretError::BothSourceAndRemote(_)) => (),
             _ => panic!(),
         }
+
+        // the standard input of a client isn't sent to its server
+        let parsing_result5 = super::super::parser::parse("--client 127.0.0.1 2021 echo").unwrap();
+        let parsing_result6 =
+            super::super::parser::parse("--client 127.0.0.1 2021 --pipe wc -l").unwrap();
+        for parsing_result in &mut [parsing_result5, parsing_result6] {
+            match interpret(&mut jm, parsing_result) {
+                Err(InterpretError::NoData(error)) => assert_eq!(error.span, Some((0, 23))),
+                _ => panic!(),
+            }
+        }
+
+        // and a server can't read its own standard input for the requests of its clients
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        jm.set_stdin(false);
+        let parsing_result7 = super::super::parser::parse("echo").unwrap();
+        let parsing_result8 = super::super::parser::parse("--pipe wc -l").unwrap();
+        for parsing_result in &mut [parsing_result7, parsing_result8] {
+            match interpret(&mut jm, parsing_result) {
+                Err(InterpretError::NoData(_)) => (),
+                _ => panic!(),
+            }
+        }
+        let mut parsing_result9 = super::super::parser::parse("echo ::: 1").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result9).is_ok());
+    }
+
+    #[test]
+    fn builder_test5() {
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        // --pipe doesn't need any value, the input is read during the execution
+        let mut parsing_result1 = super::super::parser::parse("--pipe --block 10k wc -l").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result1).is_ok());
+
+        // but values can't be given at the same time
+        let mut parsing_result2 = super::super::parser::parse("--pipe wc -l ::: 1 2").unwrap();
+        match interpret(&mut jm, &mut parsing_result2) {
+            Err(InterpretError::PipeWithValues(_)) => (),
+            _ => panic!(),
+        }
+
+        // without a shell, the command is needed to have a program to execute
+        let mut parsing_result3 = super::super::parser::parse("--pipe --no-shell").unwrap();
+        match interpret(&mut jm, &mut parsing_result3) {
+            Err(InterpretError::NoCommand(error)) => assert_eq!(error.span, Some((7, 17))),
+            _ => panic!(),
+        }
+
+        assert_eq!(parse_size("12"), 12);
+        assert_eq!(parse_size("10k"), 10 * 1024);
+        assert_eq!(parse_size("2M"), 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn build_command_test() {
+        // no target means all the values at the end
+        assert_eq!(build_command("gzip", &["a.log"], 1), "gzip a.log");
+        assert_eq!(build_command("echo {}", &["a", "1"], 1), "echo a 1");
+        assert_eq!(build_command("echo {2}-{1}", &["a", "1"], 1), "echo 1-a");
+        assert_eq!(build_command("echo {3}", &["a", "1"], 1), "echo ");
+        // the braces which aren't targets are kept
+        assert_eq!(build_command("echo {a,b}", &["1"], 1), "echo {a,b} 1");
+    }
+
+    #[test]
+    fn replacement_strings_test() {
+        let path = ["dir.d/sub/photo.tar.gz", "x"];
+        assert_eq!(
+            build_command("convert {} {.}.png", &path[..1], 1),
+            "convert dir.d/sub/photo.tar.gz dir.d/sub/photo.tar.png"
+        );
+        assert_eq!(build_command("echo {/}", &path, 1), "echo photo.tar.gz x");
+        assert_eq!(build_command("echo {1//}", &path, 1), "echo dir.d/sub");
+        assert_eq!(
+            build_command("echo {1/.} {2//}", &path, 1),
+            "echo photo.tar ."
+        );
+        assert_eq!(build_command("echo {#}-{2.}", &path, 7), "echo 7-x");
+        assert_eq!(
+            build_command("echo {%}", &path, 1),
+            format!("echo {}", SLOT_MARK)
+        );
+
+        assert_eq!(
+            build_command(
+                "mv {} {=1 s/\\.jpeg$/.jpg/ =} {= $# * 10; pad(4, 0) =}",
+                &path[..1],
+                2
+            ),
+            "mv dir.d/sub/photo.tar.gz dir.d/sub/photo.tar.gz 0020"
+        );
+        assert_eq!(
+            build_command("echo {= s/\\{.*\\}/x/; uc =}", &["a{b}"], 1),
+            "echo AX"
+        );
+        assert!(
+            CommandPattern::parse("echo {= s/a/ =}", &BTreeMap::new(), ExecMode::Shell).is_err()
+        );
+
+        assert_eq!(remove_extension("dir.d/file"), "dir.d/file");
+        assert_eq!(remove_extension(".bashrc"), ".bashrc");
+        assert_eq!(dirname("/file"), "/");
+        assert_eq!(basename("file"), "file");
+    }
+
+    #[test]
+    fn builder_test6() {
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        // the files given with :::: or --arg-file must exist
+        let mut parsing_result1 =
+            super::super::parser::parse("echo ::: 1 2 :::: unknown_file.txt").unwrap();
+        let mut parsing_result2 =
+            super::super::parser::parse("-a unknown_file.txt echo ::: 1 2").unwrap();
+        match interpret(&mut jm, &mut parsing_result1) {
+            Err(InterpretError::UnreadableFile(_)) => (),
+            _ => panic!(),
+        }
+        match interpret(&mut jm, &mut parsing_result2) {
+            Err(InterpretError::UnreadableFile(_)) => (),
+            _ => panic!(),
+        }
+
+        // "parallel echo ::::" Must pass throught parsing but not interpretation
+        let mut parsing_result3 = super::super::parser::parse("echo ::::").unwrap();
+        match interpret(&mut jm, &mut parsing_result3) {
+            Err(InterpretError::NoData(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn link_test() {
+        let separators = vec![
+            vec![String::from("a"), String::from("b"), String::from("c")],
+            vec![String::from("1"), String::from("2")],
+            vec![String::from("x")],
+        ];
+        let values = |group: &Vec<Vec<String>>| -> Vec<String> {
+            group.iter().map(|values| values.join(" ")).collect()
+        };
+
+        // ":::+" links with the previous separator, the shortest one wins
+        let groups = link_separators(separators.clone(), &[false, true, false], false);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(values(&groups[0]), vec!["a 1", "b 2"]);
+        assert_eq!(values(&groups[1]), vec!["x"]);
+
+        // "--link" links everything, the shortest ones are recycled
+        let groups = link_separators(separators, &[false, false, false], true);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(values(&groups[0]), vec!["a 1 x", "b 2 x", "c 1 x"]);
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result =
+            super::super::parser::parse("echo ::: a b ::::+ Cargo.toml ::: 1 2 :::+ 3 4").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+    }
+
+    #[test]
+    fn jobs_test() {
+        let nb_cpus = nb_cpus();
+        assert_eq!(parse_jobs("4"), 4);
+        assert_eq!(parse_jobs("0"), 0);
+        assert_eq!(parse_jobs("+2"), nb_cpus + 2);
+        assert_eq!(parse_jobs("-1"), (nb_cpus - 1).max(1));
+        assert_eq!(parse_jobs("-1000"), 1);
+        assert_eq!(parse_jobs("200%"), nb_cpus * 2);
+        assert_eq!(parse_jobs("0%"), 1);
+        assert_eq!(parse_jobs("99999999999999999999"), MAX_JOBS);
+        assert_eq!(parse_jobs("+99999999999999999999"), MAX_JOBS);
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse("-j 50% echo ::: 1 2").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+    }
+
+    #[test]
+    fn halt_test() {
+        assert_eq!(parse_halt("never"), None);
+        assert_eq!(
+            parse_halt("now,fail=1"),
+            Some(HaltPolicy {
+                when: HaltWhen::Now,
+                on_success: false,
+                limit: HaltLimit::Count(1),
+            })
+        );
+        assert_eq!(
+            parse_halt("soon,success=20%"),
+            Some(HaltPolicy {
+                when: HaltWhen::Soon,
+                on_success: true,
+                limit: HaltLimit::Percent(20),
+            })
+        );
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result =
+            super::super::parser::parse("--halt now,success=1 echo ::: 1 2").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+    }
+
+    #[test]
+    fn timeout_test() {
+        assert_eq!(
+            parse_timeout("30"),
+            Timeout::Duration(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_timeout("2.5"),
+            Timeout::Duration(Duration::from_millis(2500))
+        );
+        assert_eq!(parse_timeout("200%"), Timeout::Percent(200));
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result =
+            super::super::parser::parse("--timeout 200% echo ::: 1 2").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+    }
+
+    #[test]
+    fn joblog_test() {
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result =
+            super::super::parser::parse("--joblog unknown_directory/log echo ::: 1").unwrap();
+        match interpret(&mut jm, &mut parsing_result) {
+            Err(InterpretError::UnwritableFile(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn resume_test() {
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse("--resume echo ::: 1").unwrap();
+        match interpret(&mut jm, &mut parsing_result) {
+            Err(InterpretError::ResumeWithoutJoblog(_)) => (),
+            _ => panic!(),
+        }
+
+        // the failed jobs of the joblog are executed again with their command
+        let path = std::env::temp_dir().join(format!(
+            "rust_parallel_{}_interpreter_resume.tsv",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            format!(
+                "{}\n1\t:\t0\t0\t0\t0\t0\t0\techo ok\n2\t:\t0\t0\t0\t0\t1\t0\tfalse\n",
+                joblog::JOBLOG_HEADER
+            ),
+        )
+        .unwrap();
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let request = format!("--retry-failed --joblog {}", path);
+        let mut parsing_result = super::super::parser::parse(&request).unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        let _ = std::fs::remove_file(path);
+        assert!(format!("{}", jm).contains("/bin/bash -c false"));
+    }
+
+    #[test]
+    fn resume_interrupted_test() {
+        let args = |words: &[&str]| {
+            words
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+        };
+        let path = std::env::temp_dir().join(format!(
+            "rust_parallel_{}_interpreter_resume_interrupted.tsv",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        // the slow job is killed by the interrupt
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let command = [
+            "--joblog", path, "-j", "2", "--shell", "sleep", ":::", "0", "1",
+        ];
+        assert!(interpret_args(&mut jm, &args(&command)).is_ok());
+        let (interrupt, receiver) = tokio::sync::watch::channel(false);
+        jm.set_interrupt(receiver);
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            let _ = interrupt.send(true);
+        });
+        assert_eq!(jm.exec().ok().unwrap().len(), 1);
+        interrupter.join().unwrap();
+        let previous_jobs = joblog::read(path).unwrap();
+        assert!(previous_jobs[&1].succeeded());
+        assert!(previous_jobs[&2].killed());
+
+        // so it is executed again when the execution is resumed
+        let mut resumed = args(&command);
+        resumed.insert(0, String::from("--resume"));
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        assert!(interpret_args(&mut jm, &resumed).is_ok());
+        let outputs = jm.exec().ok().unwrap();
+        let previous_jobs = joblog::read(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(outputs.len(), 1);
+        assert!(outputs[0].succeeded());
+        assert!(previous_jobs[&2].succeeded());
+    }
+
+    #[test]
+    fn retries_test() {
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse(
+            "--retries 3 --retry-delay 0.5 --retry-backoff curl ::: a b",
+        )
+        .unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+    }
+
+    #[test]
+    fn rpl_test() {
+        let (tag, expression) = parse_rpl("{..} s/\\.[^.]+\\.[^.]+$//").ok().unwrap();
+        assert_eq!(tag, "..");
+        assert_eq!(expression.eval("dir/a.tar.gz", 1), "dir/a");
+        assert!(parse_rpl("{..}").is_err());
+        assert!(parse_rpl("{2x} uc").is_err());
+        assert!(parse_rpl("{..} s/a/").is_err());
+
+        let mut rpl = BTreeMap::new();
+        rpl.insert(tag, expression);
+        let (_, upper) = parse_rpl("{+U} uc").ok().unwrap();
+        rpl.insert(String::from("+U"), upper);
+        let command_pattern =
+            CommandPattern::parse("tar xf {1} -C {..} {2+U} {a,b}", &rpl, ExecMode::Shell)
+                .ok()
+                .unwrap();
+        assert_eq!(
+            command_pattern.build(&["a.tar.gz", "x"], 1),
+            "tar xf a.tar.gz -C a x X {a,b}"
+        );
+
+        // the definitions of GNU parallel which can't be used here are skipped
+        let path = std::env::temp_dir().join(format!(
+            "rust_parallel_{}_rpl_test_config",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "# replacement strings\n--jobs 2\n--rpl '{..} s/\\.[^.]+\\.[^.]+$//'\n\
+             --rpl '{u} $_ = uc($_)'\n--rpl {x}\n",
+        )
+        .unwrap();
+        let rpl = read_config(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(rpl.keys().collect::<Vec<&String>>(), vec![".."]);
+        assert!(read_config(&path).is_empty());
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse(
+            "--shell --rpl '{..} s/\\..*//' --rpl '{up} uc' echo {..} {1up} ::: a.b.c {x}",
+        )
+        .unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        assert!(format!("{}", jm).contains("/bin/bash -c echo a A.B.C"));
+        assert!(format!("{}", jm).contains("/bin/bash -c echo '{x}' '{X}'"));
+    }
+
+    #[test]
+    fn quote_test() {
+        assert_eq!(shell_quote("dir/file-1.txt"), "dir/file-1.txt");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's $(rm -rf ~);"), "'it'\\''s $(rm -rf ~);'");
+
+        // the values can't be interpreted by the shell
+        assert_eq!(
+            build_command("echo {} > {#}.txt", &["a b; ls"], 3),
+            "echo 'a b; ls' > 3.txt"
+        );
+        assert_eq!(build_command("echo", &["$HOME", "x"], 1), "echo '$HOME' x");
+        assert_eq!(build_command("", &["echo $HOME"], 1), " echo $HOME");
+        assert_eq!(build_command("echo {= s/a/\"/ =}", &["a"], 1), "echo '\"'");
+
+        // --quote quotes the words of the command too
+        let command_pattern =
+            CommandPattern::parse("echo a|b {} >out", &BTreeMap::new(), ExecMode::QuotedShell)
+                .ok()
+                .unwrap();
+        assert_eq!(
+            command_pattern.build(&["x y"], 1),
+            "echo 'a|b' 'x y' '>out'"
+        );
+
+        // --no-shell gives the values as arguments
+        let command_pattern =
+            CommandPattern::parse("touch  x{}.txt {#}", &BTreeMap::new(), ExecMode::NoShell)
+                .ok()
+                .unwrap();
+        assert_eq!(
+            command_pattern.build_args(&["a b"], 2),
+            vec!["touch", "xa b.txt", "2"]
+        );
+        let command_pattern = CommandPattern::parse("echo", &BTreeMap::new(), ExecMode::NoShell)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            command_pattern.build_args(&["a b", ""], 1),
+            vec!["echo", "a b", ""]
+        );
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result =
+            super::super::parser::parse("--no-shell -q printf %s ::: 1 2").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        assert!(format!("{}", jm).contains("printf %s 1"));
+        assert!(!format!("{}", jm).contains("/bin/bash"));
+
+        // the words of the command line stay single words, their backslashes are kept
+        let words = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            let request = super::super::parser::request(&args).ok().unwrap();
+            let mut pairs = super::super::parser::parse(&request).unwrap();
+            let command = pairs
+                .next()
+                .unwrap()
+                .into_inner()
+                .find(|pair| pair.as_rule() == Rule::commands)
+                .unwrap();
+            CommandPattern::parse(command.as_str(), &BTreeMap::new(), ExecMode::NoShell)
+                .ok()
+                .unwrap()
+                .build_args(&["x"], 1)
+        };
+        assert_eq!(
+            words(&["--no-shell", "touch", "my file", ":::", "x"]),
+            vec!["touch", "my file", "x"]
+        );
+        assert_eq!(
+            words(&["--no-shell", "printf", "%s\\n", "{}.txt", ":::", "x"]),
+            vec!["printf", "%s\\n", "x.txt"]
+        );
+    }
+
+    #[test]
+    fn exec_mode_test() {
+        let mode = |command_pattern: &str| match CommandPattern::parse(
+            command_pattern,
+            &BTreeMap::new(),
+            ExecMode::Auto,
+        ) {
+            Ok(command_pattern) => command_pattern.mode,
+            Err(_) => panic!("invalid command pattern {}", command_pattern),
+        };
+        // the simple commands are executed without any shell
+        assert_eq!(mode("echo"), ExecMode::NoShell);
+        assert_eq!(mode("gzip -9 {} {#}"), ExecMode::NoShell);
+        assert_eq!(mode("/bin/sh -c {}"), ExecMode::NoShell);
+        assert_eq!(mode("echo {} > out"), ExecMode::Shell);
+        assert_eq!(mode("echo $HOME {}"), ExecMode::Shell);
+        assert_eq!(mode("echo {a,b}"), ExecMode::Shell);
+        assert_eq!(mode("cd {}"), ExecMode::Shell);
+        assert_eq!(mode("{} -l"), ExecMode::Shell);
+        assert_eq!(mode("echo{}"), ExecMode::Shell);
+        // a line break separates the commands, like ";"
+        assert_eq!(mode("echo a\necho {}"), ExecMode::Shell);
+        assert_eq!(mode(""), ExecMode::Shell);
+
+        assert!(is_executable("sh"));
+        assert!(!is_executable("unknown_program"));
+        assert!(!is_executable("./Cargo.toml"));
+
+        // the values of a target are separate arguments, like with the shell
+        let command_pattern = CommandPattern::parse("mv x{}", &BTreeMap::new(), ExecMode::Auto)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            command_pattern.build_args(&["a b", "c"], 1),
+            vec!["mv", "xa b", "c"]
+        );
+        assert_eq!(command_pattern.build_args(&[], 1), vec!["mv", "x"]);
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse("echo ::: 1").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        assert!(format!("{}", jm).contains("\techo 1"));
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse("--shell echo ::: 1").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        assert!(format!("{}", jm).contains("/bin/bash -c echo 1"));
+    }
+
+    #[test]
+    fn posix_quoting_test() {
+        assert_eq!(
+            split_words(r#"a 'b c'd "e \"f\" \g" h\ i '' \'"#),
+            vec!["a", "b cd", "e \"f\" \\g", "h i", "", "'"]
+        );
+
+        // the quotes of the command are kept for the shell and the values are quoted
+        // according to the quotes around them
+        assert_eq!(
+            build_command("grep 'a b' {}", &["x y"], 1),
+            "grep 'a b' 'x y'"
+        );
+        assert_eq!(
+            build_command("echo '{}' \"{}\"", &["it's $x"], 1),
+            "echo 'it'\\''s $x' \"it's \\$x\""
+        );
+
+        let command_pattern = CommandPattern::parse(
+            "grep -e 'a b' \"{}\" x\\ y",
+            &BTreeMap::new(),
+            ExecMode::Auto,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(command_pattern.mode, ExecMode::NoShell);
+        assert_eq!(
+            command_pattern.build_args(&["1", "2"], 1),
+            vec!["grep", "-e", "a b", "1 2", "x y"]
+        );
+
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        let mut parsing_result = super::super::parser::parse(
+            "--shell grep 'a b ::: c' {} ::: 'x y' \"{}\" :::: 'unknown file'",
+        )
+        .unwrap();
+        match interpret(&mut jm, &mut parsing_result) {
+            Err(InterpretError::UnreadableFile(error)) => {
+                assert!(error.message.contains("unknown file"))
+            }
+            _ => panic!(),
+        }
+        let mut parsing_result =
+            super::super::parser::parse("--shell grep 'a b ::: c' {} ::: 'x y' \"{}\"").unwrap();
+        assert!(interpret(&mut jm, &mut parsing_result).is_ok());
+        assert!(format!("{}", jm).contains("grep 'a b ::: c' 'x y'"));
+        assert!(format!("{}", jm).contains("grep 'a b ::: c' '{}'"));
+        // an unclosed quote can't be parsed
+        assert!(super::super::parser::parse("echo 'a ::: b").is_err());
+    }
+
+    #[test]
+    fn diagnostic_test() {
+        let args = |words: &[&str]| {
+            words
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // the span of an error of the interpreter is in the request
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        match interpret_args(
+            &mut jm,
+            &args(&["--jobs", "2", "echo", "{=", "s/a/", "=}", ":::", "a"]),
+        ) {
+            Err(InterpretError::InvalidExpression(error)) => {
+                assert_eq!(error.line, "--jobs 2 echo {= s/a/ =} ::: a");
+                assert_eq!(error.span, Some((14, 24)));
+            }
+            _ => panic!(),
+        }
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        match interpret_args(&mut jm, &args(&["echo", "::::", "unknown file"])) {
+            Err(InterpretError::UnreadableFile(error)) => {
+                assert_eq!(error.span, Some((5, 24)));
+                assert!(error
+                    .to_string()
+                    .ends_with("\n    echo :::: 'unknown file'\n         ^^^^^^^^^^^^^^^^^^^"));
+            }
+            _ => panic!(),
+        }
+
+        // the errors of the parser
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        match interpret_args(&mut jm, &args(&["--dryrun", "echo", ":::", "a"])) {
+            Err(InterpretError::Parse(error)) => {
+                assert_eq!(error.diagnostic().suggestion.as_deref(), Some("--dry-run"))
+            }
+            _ => panic!(),
+        }
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        assert!(interpret_args(&mut jm, &args(&["echo", "{}", ":::", "a"])).is_ok());
+
+        // the options which can't be used together are both underlined
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        match interpret_args(
+            &mut jm,
+            &args(&[
+                "--server", "8000", "-j", "2", "--client", "host", "8000", "echo",
+            ]),
+        ) {
+            Err(InterpretError::BothSourceAndRemote(error)) => {
+                assert_eq!(error.span, Some((19, 37)));
+                assert_eq!(error.related.to_vec(), vec![(0, 13)]);
+            }
+            _ => panic!(),
+        }
+
+        // a value starting with digits is a single value
+        let mut jm = JobManager::new(String::from("/bin/bash"));
+        assert!(
+            interpret_args(&mut jm, &args(&["echo", ":::", "0.3", "2021-01-01", "10k"])).is_ok()
+        );
+        let jobs = format!("{}", jm);
+        assert_eq!(jobs.matches("\n\techo").count(), 3);
+        assert!(jobs.contains("echo 0.3") && jobs.contains("echo 2021-01-01"));
+        assert!(jobs.contains("echo 10k"));
     }
 }
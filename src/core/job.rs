@@ -1,14 +1,117 @@
 extern crate tokio;
 use log::debug;
 use std::fmt;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::process;
+use std::process::{ExitStatus, Stdio};
 use std::thread;
-use tokio::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::time;
+
+/// Environment variable giving the attempt number (from 1) to the command, it grows when the job is retried.
+pub const ATTEMPT_VAR: &str = "PARALLEL_ATTEMPT";
+
+/// Environment variable giving the job slot number (from 1) to the command.
+pub const SLOT_VAR: &str = "PARALLEL_JOBSLOT";
+
+/// Placeholder of the job slot number ({%}) in the command, only known once the job starts.
+/// A command can't contain a nul character, so it can't be mistaken for a value.
+pub const SLOT_MARK: &str = "\u{0}{%}\u{0}";
+
+/**
+ * Return the word quoted for the shell, so the shell gives it as it is to the command (ex: "a b" gives "'a b'").
+ * The words made of safe characters only are kept as they are.
+ * # Arguments
+ * - `word` - the word to quote, ex: a value replacing a target of the command
+ */
+pub fn shell_quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return String::from(word);
+    }
+    // a quote can't be in a quoted string, it is closed, escaped and opened again
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Time given to a timed out command to end after being asked to (TERM) before being killed (KILL).
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/**
+ * Output of an executed job, kept as raw bytes as a command can write anything :
+ * - `stdout : Vec<u8>` - what the command wrote on its standard output
+ * - `stderr : Vec<u8>` - what the command wrote on its standard error (or the reason why it couldn't be executed)
+ * - `status : Option<ExitStatus>` - how the command ended, None if it couldn't be executed
+ * - `start : Option<SystemTime>` - when the command started, None if it wasn't executed by a job
+ * - `runtime : Duration` - how long the command ran
+ * - `timed_out : bool` - the command was stopped because it ran longer than its time limit (--timeout)
+ * - `attempt : usize` - the attempt of the job which gave this output, from 1 (--retries)
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JobOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub status: Option<ExitStatus>,
+    pub start: Option<SystemTime>,
+    pub runtime: Duration,
+    pub timed_out: bool,
+    pub attempt: usize,
+}
+
+impl JobOutput {
+    /**
+     * Return the output of a command killed (KILL) before its end because the execution stopped,
+     * ex: when it halts now or is interrupted. What the command wrote is lost with it.
+     * # Arguments
+     * - `start` - when the command started
+     * - `runtime` - how long the command ran before being killed
+     * - `attempt` - the attempt of the job which was killed, from 1
+     */
+    pub fn killed(start: SystemTime, runtime: Duration, attempt: usize) -> JobOutput {
+        JobOutput {
+            status: Some(ExitStatus::from_raw(libc::SIGKILL)),
+            start: Some(start),
+            runtime,
+            attempt,
+            ..Default::default()
+        }
+    }
+
+    /**
+     * Return true if the command was executed and ended successfully.
+     */
+    pub fn succeeded(&self) -> bool {
+        match self.status {
+            Some(status) => status.success(),
+            None => false,
+        }
+    }
+
+    /**
+     * Return the exit code of the command, -1 if it was killed by a signal or couldn't be executed.
+     */
+    pub fn exit_value(&self) -> i32 {
+        self.status.and_then(|status| status.code()).unwrap_or(-1)
+    }
+
+    /**
+     * Return the signal which killed the command, 0 if it wasn't killed.
+     */
+    pub fn signal(&self) -> i32 {
+        self.status.and_then(|status| status.signal()).unwrap_or(0)
+    }
+}
 
 /**
  * Representation of the command execution environment :
  * - `cmd : String` - linux command name
  * - `parameter: Vec<String>` - list of command parameters
+ * - `stdin: Option<Vec<u8>>` - data written to the standard input of the command (used by `--pipe`)
+ * - `attempt: usize` - the number of times the job has been executed, from 1 (used by `--retries`)
+ * - `slot: Option<usize>` - the job slot given by the job manager when the job starts, replaces `SLOT_MARK` in the command
  * # Example
  * ```rust
  * use rust_parallel::core::job::Job;
@@ -21,9 +124,13 @@ use tokio::process::Command;
  * job.exec();
  * ```
  */
+#[derive(Clone)]
 pub struct Job {
     cmd: String,
     parameter: Vec<String>,
+    stdin: Option<Vec<u8>>,
+    attempt: usize,
+    slot: Option<usize>,
 }
 
 /***
@@ -34,10 +141,10 @@ impl fmt::Display for Job {
         let _r = write!(
             f,
             r"{}{}",
-            self.cmd,
+            self.expand(&self.cmd),
             self.parameter
                 .iter()
-                .fold(String::new(), |acc, arg| acc + " " + arg)
+                .fold(String::new(), |acc, arg| acc + " " + &self.expand(arg))
         );
         Ok(())
     }
@@ -59,7 +166,86 @@ impl Job {
             parameter.push(args[i].clone());
         }
 
-        Job { cmd, parameter }
+        Job {
+            cmd,
+            parameter,
+            stdin: None,
+            attempt: 1,
+            slot: None,
+        }
+    }
+
+    /**
+     * Set the data that will be written to the standard input of the command.
+     * Without it, the command gets an empty standard input.
+     * # Arguments
+     * - `data` - the bytes to feed to the command
+     */
+    pub fn set_stdin(&mut self, data: Vec<u8>) {
+        self.stdin = Some(data);
+    }
+
+    /**
+     * Return the attempt number of the job, from 1.
+     */
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /**
+     * Return the command as a shell would execute it : the command given to the shell for a job executed
+     * by a shell (`SHELL -c COMMAND`), the quoted words of the command line otherwise.
+     */
+    pub fn command_line(&self) -> String {
+        match self.parameter.as_slice() {
+            [option, command] if option == "-c" => self.expand(command),
+            _ => std::iter::once(&self.cmd)
+                .chain(&self.parameter)
+                .map(|word| shell_quote(&self.expand(word)))
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+
+    /**
+     * Return the number of bytes written to the standard input of the command.
+     */
+    pub fn input_len(&self) -> usize {
+        self.stdin.as_ref().map_or(0, |data| data.len())
+    }
+
+    /**
+     * Set the attempt number of the job before it is executed again.
+     * # Arguments
+     * - `attempt` - the new attempt number, given to the command by the `PARALLEL_ATTEMPT` variable
+     */
+    pub fn set_attempt(&mut self, attempt: usize) {
+        self.attempt = attempt;
+    }
+
+    /**
+     * Set the job slot of the job before it is executed, the number of the place it takes among the running jobs.
+     * # Arguments
+     * - `slot` - the job slot, from 1, given to the command by `{%}` and the `PARALLEL_JOBSLOT` variable
+     */
+    pub fn set_slot(&mut self, slot: usize) {
+        self.slot = Some(slot);
+    }
+
+    /**
+     * Private function.
+     *
+     * Return a word of the command with the job slot in place of its placeholder,
+     * "{%}" if the slot isn't known yet.
+     */
+    fn expand(&self, word: &str) -> String {
+        if !word.contains(SLOT_MARK) {
+            return String::from(word);
+        }
+        match self.slot {
+            Some(slot) => word.replace(SLOT_MARK, &slot.to_string()),
+            None => word.replace(SLOT_MARK, "{%}"),
+        }
     }
 
     /**
@@ -68,37 +254,200 @@ impl Job {
      * either the standard output if the execution was successful or an error.
      */
     pub async fn exec(&mut self) -> Result<process::Output, std::io::Error> {
-        debug!("{} {:?}", process::id(), thread::current().id());
+        let (child, group) = self.spawn()?;
+        let output = child.wait_with_output().await;
+        group.release();
+        output
+    }
+
+    /**
+     * Execute the current job, stopping it if it runs longer than its time limit :
+     * the process group of the command is asked to end (TERM), then killed (KILL) after a grace period.
+     * # Arguments
+     * - `time_limit` - the maximal runtime of the command (None = unlimited), it may change while the command runs
+     * # Return
+     * the output of the command, an error is given on its standard error.
+     */
+    pub async fn run(&mut self, time_limit: watch::Receiver<Option<Duration>>) -> JobOutput {
+        let start_time = SystemTime::now();
+        let start = Instant::now();
+        let result = self.wait_limited(time_limit, start).await;
+        let runtime = start.elapsed();
 
-        // Create a new tokio command with the linux command name
-        let mut command: Command = Command::new(self.cmd.clone());
+        match result {
+            // the command was executed
+            Ok((output, timed_out)) => JobOutput {
+                stdout: output.stdout,
+                stderr: output.stderr,
+                status: Some(output.status),
+                start: Some(start_time),
+                runtime,
+                timed_out,
+                attempt: self.attempt,
+            },
+            // the command is uncorrect
+            Err(e) => JobOutput {
+                stderr: format!("{}\n", e).into_bytes(),
+                start: Some(start_time),
+                runtime,
+                attempt: self.attempt,
+                ..Default::default()
+            },
+        }
+    }
+
+    /**
+     * Private function.
+     *
+     * Wait for the end of the command started at `start`, or stop it once its time limit is exceeded.
+     * Return its output and true if it timed out.
+     */
+    async fn wait_limited(
+        &mut self,
+        mut time_limit: watch::Receiver<Option<Duration>>,
+        start: Instant,
+    ) -> Result<(process::Output, bool), std::io::Error> {
+        let (child, group) = self.spawn()?;
+        let output = child.wait_with_output();
+        tokio::pin!(output);
+
+        let mut limit_may_change = true;
+        loop {
+            let remaining = time_limit
+                .borrow()
+                .map(|limit| limit.saturating_sub(start.elapsed()));
+            tokio::select! {
+                output = &mut output => {
+                    group.release();
+                    return output.map(|output| (output, false));
+                }
+                _ = time::sleep(remaining.unwrap_or_default()), if remaining.is_some() => break,
+                // the limit is checked again when it changes
+                changed = time_limit.changed(), if limit_may_change => {
+                    limit_may_change = changed.is_ok();
+                }
+            }
+        }
+
+        debug!("<{}> timed out", self);
+        group.signal(libc::SIGTERM);
+        let output = match time::timeout(KILL_GRACE_PERIOD, &mut output).await {
+            Ok(output) => output,
+            Err(_) => {
+                debug!("<{}> killed", self);
+                group.signal(libc::SIGKILL);
+                output.await
+            }
+        };
+        group.release();
+        output.map(|output| (output, true))
+    }
+
+    /**
+     * Private function.
+     *
+     * Start the command in its own process group, so it can be stopped with all its children.
+     * Its standard output and standard error are captured, and its standard input is fed with `stdin`.
+     */
+    fn spawn(&self) -> Result<(Child, ProcessGroup), std::io::Error> {
+        debug!("{} {:?}", process::id(), thread::current().id());
 
-        // Add parameters to the command
-        for arg in &self.parameter {
-            command.arg(&arg.clone());
+        // Create a new command with the linux command name and its parameters
+        let mut command = process::Command::new(self.expand(&self.cmd));
+        command
+            .args(self.parameter.iter().map(|arg| self.expand(arg)))
+            .env(ATTEMPT_VAR, self.attempt.to_string())
+            .process_group(0);
+        if let Some(slot) = self.slot {
+            command.env(SLOT_VAR, slot.to_string());
         }
 
-        // A future is a value that may not have finished computing yet.
-        // This kind of "asynchronous value" makes it possible for a thread to continue doing useful work
-        // while it waits for the value to become available.
-        let future = command.output();
+        // A tokio command is waited asynchronously,
+        // it is killed if the execution is dropped before its end
+        let mut command: Command = Command::from(command);
+        command
+            .kill_on_drop(true)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // the data is kept as the job may be executed again
+        let data = self.stdin.clone();
+        match data {
+            None => command.stdin(Stdio::null()),
+            // The command reads its input from us, so the pipe must be created before spawning it
+            Some(_) => command.stdin(Stdio::piped()),
+        };
+        let mut child = command.spawn()?;
+        let group = ProcessGroup::new(&child);
         debug!("<{}> spawn", self);
 
-        // Wait for the result of the command execution
-        let future_result = future.await;
-        match future_result {
-            Err(e) => return Err(e),
-            Ok(o) => return Ok(o),
+        if let Some(data) = data {
+            // The data is written from another task so a command producing a lot of output
+            // before reading all its input can't deadlock with us.
+            // Dropping the pipe at the end closes the standard input of the command.
+            let mut pipe = child.stdin.take().unwrap();
+            tokio::spawn(async move {
+                let _ = pipe.write_all(&data).await;
+            });
         }
 
-        // if there was no error during execution then the output is returned
+        Ok((child, group))
+    }
+}
+
+/**
+ * Process group of a started command, the command and all its children are killed
+ * if it is dropped before being released (when the execution is stopped before the end of the command) :
+ * - `id : Option<i32>` - the identifier of the group, None once released
+ */
+struct ProcessGroup {
+    id: Option<i32>,
+}
+
+impl ProcessGroup {
+    /**
+     * Return the group of the given command, which is the leader of its own group.
+     */
+    fn new(child: &Child) -> ProcessGroup {
+        ProcessGroup {
+            id: child.id().map(|id| id as i32),
+        }
+    }
+
+    /**
+     * Send a signal to all the processes of the group.
+     */
+    fn signal(&self, signal: i32) {
+        if let Some(id) = self.id {
+            // a negative pid targets the whole process group
+            if unsafe { libc::kill(-id, signal) } != 0 {
+                debug!(
+                    "signal {} to group {}: {}",
+                    signal,
+                    id,
+                    io::Error::last_os_error()
+                );
+            }
+        }
+    }
+
+    /**
+     * Forget the group once the command ended, its identifier may be reused.
+     */
+    fn release(mut self) {
+        self.id = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.signal(libc::SIGKILL);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::process::Child;
     use tokio::runtime::{Builder, Runtime};
 
     fn init(nb_thread: Option<usize>) -> Runtime {
@@ -151,4 +500,14 @@ mod tests {
             let _cmd: Child = Command::new(String::from("unknown_cmd")).spawn().unwrap();
         });
     }
+
+    #[test]
+    fn job_command_line() {
+        let args = |words: &[&str]| words.iter().map(|word| word.to_string()).collect();
+        let job = Job::new(args(&["/bin/sh", "-c", "echo 'a b' > out"]));
+        assert_eq!(job.command_line(), "echo 'a b' > out");
+        // without a shell, the words are quoted so the command line can be executed again
+        let job = Job::new(args(&["echo", "a b", "it's", ""]));
+        assert_eq!(job.command_line(), "echo 'a b' 'it'\\''s' ''");
+    }
 }
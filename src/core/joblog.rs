@@ -0,0 +1,303 @@
+use super::job::JobOutput;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// The columns of the joblog, in the same order as GNU parallel.
+pub const JOBLOG_HEADER: &str =
+    "Seq\tHost\tStarttime\tJobRuntime\tSend\tReceive\tExitval\tSignal\tCommand";
+
+/// The host written in the joblog for the jobs executed locally.
+const LOCAL_HOST: &str = ":";
+
+/**
+ * Record of the executed jobs (--joblog), one tab separated line per job written as soon as it ends :
+ * - `file : Mutex<File>` - the joblog, shared by the jobs ending at the same time
+ *
+ * Each line gives the sequence number of the job (from 1), the host, the start time (seconds since the epoch),
+ * the runtime (seconds), the number of bytes sent to the job and received from it, its exit value, the signal
+ * which killed it and its command, whose backslashes and line breaks are escaped (`\\`, `\n` and `\r`).
+ */
+pub struct JobLog {
+    file: Mutex<File>,
+}
+
+impl JobLog {
+    /**
+     * Create the joblog (or truncate it if it already exists) and write its header.
+     * # Arguments
+     * - `path` - the file of the joblog
+     */
+    pub fn create(path: &str) -> io::Result<JobLog> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", JOBLOG_HEADER)?;
+        Ok(JobLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    /**
+     * Open the joblog to add the jobs of a resumed execution after the previous ones,
+     * it is created (with its header) if it doesn't exist.
+     * # Arguments
+     * - `path` - the file of the joblog
+     */
+    pub fn append(path: &str) -> io::Result<JobLog> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", JOBLOG_HEADER)?;
+        }
+        Ok(JobLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    /**
+     * Write the line of an ended job.
+     * # Arguments
+     * - `seq` - the sequence number of the job, from 1
+     * - `command` - the executed command
+     * - `sent` - the number of bytes written to the standard input of the job
+     * - `output` - the output of the job
+     */
+    pub fn record(&self, seq: usize, command: &str, sent: usize, output: &JobOutput) {
+        let line = format_line(seq, command, sent, output);
+        // a line is written at once so the lines of the jobs ending together aren't mixed,
+        // a joblog which can't be written doesn't stop the execution
+        let _ = self.file.lock().unwrap().write_all(line.as_bytes());
+    }
+}
+
+/**
+ * A job recorded in a joblog :
+ * - `seq : usize` - the sequence number of the job, from 1
+ * - `exit_value : i32` - its exit value, -1 if it was killed or couldn't be executed
+ * - `signal : i32` - the signal which killed it, 0 if it wasn't killed
+ * - `command : String` - the executed command
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobLogEntry {
+    pub seq: usize,
+    pub exit_value: i32,
+    pub signal: i32,
+    pub command: String,
+}
+
+impl JobLogEntry {
+    /**
+     * Return true if the recorded job ended successfully.
+     */
+    pub fn succeeded(&self) -> bool {
+        self.exit_value == 0 && self.signal == 0
+    }
+
+    /**
+     * Return true if the recorded job was killed (KILL) before its end, ex: by the halt or an interrupt,
+     * so it didn't complete.
+     */
+    pub fn killed(&self) -> bool {
+        self.exit_value == -1 && self.signal == libc::SIGKILL
+    }
+
+    /**
+     * Private function.
+     *
+     * Return the entry recorded by a line of a joblog, None for the header or a malformed line.
+     */
+    fn parse(line: &str) -> Option<JobLogEntry> {
+        let columns: Vec<&str> = line.splitn(9, '\t').collect();
+        if columns.len() != 9 {
+            return None;
+        }
+        Some(JobLogEntry {
+            seq: columns[0].parse().ok()?,
+            exit_value: columns[6].parse().ok()?,
+            signal: columns[7].parse().ok()?,
+            command: unescape(columns[8]),
+        })
+    }
+}
+
+/**
+ * Read the jobs recorded in a joblog, by sequence number.
+ * When a job is recorded several times (it was executed again), its last line is kept.
+ * A missing joblog records no job.
+ * # Arguments
+ * - `path` - the file of the joblog
+ */
+pub fn read(path: &str) -> io::Result<BTreeMap<usize, JobLogEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut entries = BTreeMap::new();
+    for line in BufReader::new(file).lines() {
+        if let Some(entry) = JobLogEntry::parse(&line?) {
+            entries.insert(entry.seq, entry);
+        }
+    }
+    Ok(entries)
+}
+
+/**
+ * Private function.
+ *
+ * Return the line of the joblog for an ended job, with its end of line.
+ */
+fn format_line(seq: usize, command: &str, sent: usize, output: &JobOutput) -> String {
+    let start = output
+        .start
+        .and_then(|start| start.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "{}\t{}\t{:.3}\t{:.3}\t{}\t{}\t{}\t{}\t{}\n",
+        seq,
+        LOCAL_HOST,
+        start.as_secs_f64(),
+        output.runtime.as_secs_f64(),
+        sent,
+        output.stdout.len() + output.stderr.len(),
+        output.exit_value(),
+        output.signal(),
+        escape(command),
+    )
+}
+
+/**
+ * Private function.
+ *
+ * Return the command escaped to fit in a line of the joblog.
+ */
+fn escape(command: &str) -> String {
+    let mut escaped = String::with_capacity(command.len());
+    for c in command.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/**
+ * Private function.
+ *
+ * Return the command of a line of the joblog, the reverse of `escape`.
+ * An unknown escape is kept as it is.
+ */
+fn unescape(column: &str) -> String {
+    let mut command = String::with_capacity(column.len());
+    let mut chars = column.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            command.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => command.push('\\'),
+            Some('n') => command.push('\n'),
+            Some('r') => command.push('\r'),
+            Some(other) => {
+                command.push('\\');
+                command.push(other);
+            }
+            None => command.push('\\'),
+        }
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::time::Duration;
+
+    #[test]
+    fn joblog_line() {
+        let output = JobOutput {
+            stdout: b"hello\n".to_vec(),
+            start: Some(UNIX_EPOCH + Duration::from_millis(1500)),
+            runtime: Duration::from_millis(250),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_line(3, "echo hello", 0, &output),
+            "3\t:\t1.500\t0.250\t0\t6\t-1\t0\techo hello\n"
+        );
+    }
+
+    #[test]
+    fn joblog_escape() {
+        let output = JobOutput::default();
+        assert_eq!(
+            format_line(1, "echo a\necho 'b\\n'", 0, &output),
+            "1\t:\t0.000\t0.000\t0\t0\t-1\t0\techo a\\necho 'b\\\\n'\n"
+        );
+        for command in &["echo a\necho b", "printf 'a\\n'\r", "echo \\", "a\tb"] {
+            assert_eq!(unescape(&escape(command)), *command);
+        }
+        assert_eq!(unescape("a\\tb\\"), "a\\tb\\");
+    }
+
+    #[test]
+    fn joblog_file() {
+        let path = std::env::temp_dir().join(format!(
+            "rust_parallel_{}_joblog_test.tsv",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let joblog = JobLog::create(path).unwrap();
+        joblog.record(1, "true", 0, &JobOutput::default());
+        let lines = std::fs::read_to_string(path).unwrap();
+        assert!(lines.starts_with(JOBLOG_HEADER));
+        assert_eq!(lines.lines().count(), 2);
+        let _ = std::fs::remove_file(path);
+        assert!(JobLog::create("unknown_directory/joblog").is_err());
+    }
+
+    #[test]
+    fn joblog_read() {
+        let path = std::env::temp_dir().join(format!(
+            "rust_parallel_{}_joblog_read.tsv",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        assert!(read(path).unwrap().is_empty());
+
+        let failed = JobOutput {
+            status: Some(std::process::ExitStatus::from_raw(256)),
+            ..Default::default()
+        };
+        let succeeded = JobOutput {
+            status: Some(std::process::ExitStatus::from_raw(0)),
+            ..Default::default()
+        };
+        let joblog = JobLog::append(path).unwrap();
+        joblog.record(1, "false", 0, &failed);
+        joblog.record(2, "echo a\tb\necho \\n", 0, &succeeded);
+        // a resumed execution adds its lines without a new header
+        let joblog = JobLog::append(path).unwrap();
+        joblog.record(1, "false", 0, &succeeded);
+
+        let entries = read(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[&1].succeeded());
+        assert_eq!(
+            entries[&2],
+            JobLogEntry {
+                seq: 2,
+                exit_value: 0,
+                signal: 0,
+                command: String::from("echo a\tb\necho \\n"),
+            }
+        );
+    }
+}
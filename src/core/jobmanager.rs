@@ -1,46 +1,454 @@
-use super::job::Job;
+use super::job::{Job, JobOutput};
+use super::joblog::JobLog;
+use crate::error::Error;
 use crate::remote::client::ParallelClient;
 use crate::remote::server::ParallelServer;
+use crate::remote::REQUEST_SEPARATOR;
 use futures::future;
 use log::debug;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
 use std::fmt;
+use std::io::{self, Write};
 use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::runtime::Handle;
 use tokio::runtime::{Builder, Runtime};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
+use tokio::time;
+
+/// Maximal number of jobs started but not displayed yet with keep order (if it is greater than the number of jobs).
+const REORDER_BUFFER_SIZE: usize = 1024;
+
+/// Maximal number of jobs running at the same time, a greater number is reduced to it : half of the
+/// maximal number of permits of a tokio semaphore (`usize::MAX >> 3`), as tokio 1.4 checks the permits
+/// given back against the doubled count it stores.
+pub const MAX_JOBS: usize = usize::MAX >> 4;
+
+/// Jobs created lazily while the job manager is running, for example from the standard input.
+pub type JobSource = Box<dyn Iterator<Item = Job> + Send>;
+
+/**
+ * Return the number of CPUs of the machine, the default number of jobs running at the same time.
+ */
+pub fn nb_cpus() -> usize {
+    thread::available_parallelism()
+        .map(|nb| nb.get())
+        .unwrap_or(1)
+}
+
+/// Shell executing the commands when the SHELL environment variable isn't set.
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
+/**
+ * Return the shell executing the commands of the jobs : the one of the SHELL environment variable,
+ * or `/bin/sh` without it (ex: in a container).
+ */
+pub fn user_shell() -> String {
+    match std::env::var("SHELL") {
+        Ok(shell) if !shell.is_empty() => shell,
+        _ => String::from(DEFAULT_SHELL),
+    }
+}
+
+/// Exit code of the program when an error prevents it from running the jobs.
+pub const ERROR_EXIT_CODE: i32 = 255;
+
+/// Exit code of the program when more than 100 jobs failed.
+const MAX_FAILED_EXIT_CODE: usize = 101;
+
+/**
+ * Return the exit code of the program for the results of its jobs, like GNU parallel :
+ * 0 if all jobs succeeded, otherwise the number of failed jobs (101 if more than 100 failed).
+ */
+pub fn exit_code(outputs: &[JobOutput]) -> i32 {
+    let nb_failed = outputs.iter().filter(|output| !output.succeeded()).count();
+    nb_failed.min(MAX_FAILED_EXIT_CODE) as i32
+}
+
+/// When the execution stops once the halt condition is reached (--halt).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HaltWhen {
+    /// no new job is started, the running ones are waited
+    Soon,
+    /// the running jobs are killed
+    Now,
+}
+
+/// The number of jobs reaching the halt condition, either a number of jobs or a percentage of all the jobs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HaltLimit {
+    Count(usize),
+    Percent(usize),
+}
+
+/**
+ * Policy stopping the execution early (--halt) :
+ * - `when : HaltWhen` - what happens to the running jobs when the condition is reached
+ * - `on_success : bool` - the succeeded jobs are counted instead of the failed ones
+ * - `limit : HaltLimit` - the number of counted jobs reaching the condition
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HaltPolicy {
+    pub when: HaltWhen,
+    pub on_success: bool,
+    pub limit: HaltLimit,
+}
+
+impl HaltPolicy {
+    /**
+     * Return true if the execution must stop.
+     * # Arguments
+     * - `nb_succeeded` - the number of succeeded jobs
+     * - `nb_failed` - the number of failed jobs
+     * - `nb_jobs` - the number of jobs, used by a percentage
+     */
+    pub fn reached(&self, nb_succeeded: usize, nb_failed: usize, nb_jobs: usize) -> bool {
+        let count = if self.on_success {
+            nb_succeeded
+        } else {
+            nb_failed
+        };
+        match self.limit {
+            HaltLimit::Count(limit) => count >= limit.max(1),
+            // compared in u128 so that a huge percentage or number of jobs can't overflow
+            HaltLimit::Percent(percent) => {
+                count > 0 && count as u128 * 100 >= percent as u128 * nb_jobs as u128
+            }
+        }
+    }
+}
+
+/// The maximal runtime of a job (--timeout), either a duration or a percentage of the median runtime
+/// of the completed jobs (no limit until a job completed).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timeout {
+    Duration(Duration),
+    Percent(usize),
+}
+
+/**
+ * Policy executing again the failed jobs (--retries) :
+ * - `retries : usize` - the maximal number of times a failed job is executed again
+ * - `delay : Duration` - the time waited before executing again a failed job
+ * - `backoff : bool` - the delay is doubled after each failed attempt
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: usize,
+    pub delay: Duration,
+    pub backoff: bool,
+}
+
+impl RetryPolicy {
+    /**
+     * Return true if a job failing at the given attempt must be executed again.
+     * # Arguments
+     * - `attempt` - the attempt which failed, from 1
+     */
+    pub fn retry(&self, attempt: usize) -> bool {
+        attempt <= self.retries
+    }
+
+    /**
+     * Return the time to wait before executing again a job which failed at the given attempt.
+     * # Arguments
+     * - `attempt` - the attempt which failed, from 1
+     */
+    pub fn delay(&self, attempt: usize) -> Duration {
+        if !self.backoff {
+            return self.delay;
+        }
+        let factor = u32::try_from(attempt - 1)
+            .ok()
+            .and_then(|exponent| 2u32.checked_pow(exponent));
+        factor
+            .and_then(|factor| self.delay.checked_mul(factor))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// Where the outputs of the jobs go, their status is always returned by `JobManager::exec`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputMode {
+    /// the outputs are written on the standard output and the standard error, then dropped
+    #[default]
+    Display,
+    /// the outputs are only returned with the status, ex: to a program embedding the job manager
+    Collect,
+}
+
+/**
+ * The execution options of a job manager, given by name then checked by `JobManager::set_options` :
+ * - `jobs : Option<usize>` - the maximal number of jobs running at the same time (None = the number of CPUs, 0 = unlimited)
+ * - `dry_run : bool` - the commands are only displayed
+ * - `keep_order : bool` - the outputs are displayed in the order of the jobs instead of as soon as they end
+ * - `output : OutputMode` - the outputs are displayed or only returned
+ * - `halt : Option<HaltPolicy>` - the condition stopping the execution early
+ * - `timeout : Option<Timeout>` - the maximal runtime of the jobs
+ * - `retry : Option<RetryPolicy>` - how the failed jobs are executed again
+ * - `server : Option<usize>` - the port listened as a remote executor
+ * - `client : Option<(String, usize)>` - the address and the port of the remote executor running the jobs
+ *
+ * The joblog, the jobs skipped by a resume and the input source stay outside, with `JobManager::set_joblog`,
+ * `set_skip` and `set_source` : they aren't options but what a single execution reads and writes
+ * (an open file, the jobs of a previous execution, an iterator consumed by the execution),
+ * so they can't be cloned, compared or given to several job managers like the options.
+ * # Example
+ * ```rust
+ * use rust_parallel::core::jobmanager::{ExecOptions, JobManager};
+ * let mut options = ExecOptions::new();
+ * options.jobs(5).keep_order(true); //5 jobs at a time, keep order
+ * let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+ * jobmanager.set_options(&options).unwrap();
+ * ```
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExecOptions {
+    jobs: Option<usize>,
+    dry_run: bool,
+    keep_order: bool,
+    output: OutputMode,
+    halt: Option<HaltPolicy>,
+    timeout: Option<Timeout>,
+    retry: Option<RetryPolicy>,
+    server: Option<usize>,
+    client: Option<(String, usize)>,
+}
+
+impl ExecOptions {
+    /**
+     * Return the default options : as many jobs as CPUs, executed and displayed as soon as they end.
+     */
+    pub fn new() -> ExecOptions {
+        ExecOptions::default()
+    }
+
+    /**
+     * Set the maximal number of jobs running at the same time.
+     * # Arguments
+     * - `nb` - the number of jobs, 0 means unlimited, at most `MAX_JOBS`
+     */
+    pub fn jobs(&mut self, nb: usize) -> &mut ExecOptions {
+        self.jobs = Some(nb.min(MAX_JOBS));
+        self
+    }
+
+    /**
+     * Set if the commands are only displayed instead of being executed.
+     */
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut ExecOptions {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /**
+     * Set if the outputs are displayed in the order of the jobs instead of as soon as they end.
+     */
+    pub fn keep_order(&mut self, keep_order: bool) -> &mut ExecOptions {
+        self.keep_order = keep_order;
+        self
+    }
+
+    /**
+     * Set if the outputs of the jobs are displayed or only returned.
+     */
+    pub fn output(&mut self, output: OutputMode) -> &mut ExecOptions {
+        self.output = output;
+        self
+    }
+
+    /**
+     * Set the condition stopping the execution once enough jobs failed or succeeded.
+     */
+    pub fn halt(&mut self, halt: HaltPolicy) -> &mut ExecOptions {
+        self.halt = Some(halt);
+        self
+    }
+
+    /**
+     * Set the maximal runtime of each job.
+     */
+    pub fn timeout(&mut self, timeout: Timeout) -> &mut ExecOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /**
+     * Set how many times and when the failed jobs are executed again.
+     */
+    pub fn retries(&mut self, retry: RetryPolicy) -> &mut ExecOptions {
+        self.retry = Some(retry);
+        self
+    }
+
+    /**
+     * Set the port listened to execute the requests of the clients, as a remote executor.
+     */
+    pub fn server(&mut self, port: usize) -> &mut ExecOptions {
+        self.server = Some(port);
+        self
+    }
+
+    /**
+     * Set the remote executor executing the jobs.
+     * # Arguments
+     * - `address` - the address of the server
+     * - `port` - the port listened by the server
+     */
+    pub fn client(&mut self, address: &str, port: usize) -> &mut ExecOptions {
+        self.client = Some((String::from(address), port));
+        self
+    }
+
+    /**
+     * Return an error if some options can't be used together.
+     */
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.server.is_some() && self.client.is_some() {
+            return Err(OptionsError::ServerAndClient);
+        }
+        Ok(())
+    }
+}
+
+/// The execution options which can't be used together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionsError {
+    ServerAndClient,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::ServerAndClient => {
+                write!(
+                    f,
+                    "You can't be the server and the client at the same time."
+                )
+            }
+        }
+    }
+}
+
+/**
+ * The places of the jobs running at the same time, a running job takes one :
+ * - `limit : Option<Arc<Semaphore>>` - a permit per place if the number of jobs is limited, closed when the execution halts
+ * - `used : Mutex<BTreeSet<usize>>` - the numbers of the places taken, the job slots
+ */
+struct Slots {
+    limit: Option<Arc<Semaphore>>,
+    used: Mutex<BTreeSet<usize>>,
+}
+
+impl Slots {
+    /**
+     * Return the places of `nb_jobs` jobs running at the same time, None = unlimited.
+     */
+    fn new(nb_jobs: Option<usize>) -> Slots {
+        Slots {
+            limit: nb_jobs.map(|nb| Arc::new(Semaphore::new(nb))),
+            used: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /**
+     * Wait for a free place and take it, with the smallest free number.
+     * Return None if the places are closed.
+     */
+    async fn take(self: &Arc<Self>) -> Option<Slot> {
+        let permit = match &self.limit {
+            Some(limit) => Some(limit.clone().acquire_owned().await.ok()?),
+            None => None,
+        };
+        let mut used = self.used.lock().unwrap();
+        let number = (1..).find(|number| !used.contains(number)).unwrap();
+        used.insert(number);
+        Some(Slot {
+            number,
+            slots: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    /**
+     * Close the places, no more job can start.
+     */
+    fn close(&self) {
+        if let Some(limit) = &self.limit {
+            limit.close();
+        }
+    }
+}
+
+/**
+ * A place taken by a running job, it is given back when dropped :
+ * - `number : usize` - the job slot, from 1
+ * - `slots : Arc<Slots>` - the places it belongs to
+ * - `_permit : Option<OwnedSemaphorePermit>` - the permit of the place if the number of jobs is limited
+ */
+struct Slot {
+    number: usize,
+    slots: Arc<Slots>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.slots.used.lock().unwrap().remove(&self.number);
+    }
+}
 
 /**
  * Representation of the command execution environment :
  * - `cmds : Vec<Job>` - the list of commands to be executed
- * - `nb_thread : Option<usize>` - the number of threads to be used in the execution environment
+ * - `source : Option<JobSource>` - the commands to be executed after `cmds`, created during the execution
+ * - `nb_jobs : Option<usize>` - the maximal number of jobs running at the same time (None = unlimited)
  * - `dry_run : bool` - execution parameter allowing only to display the commands without executing them
  * - `keep_order : bool` - execution parameter allowing to display the returns of the commands in the execution order given in input
+ * - `halt : Option<HaltPolicy>` - execution parameter allowing to stop the execution once enough jobs failed or succeeded
+ * - `timeout : Option<Timeout>` - execution parameter allowing to stop the jobs running for too long
+ * - `retry : Option<RetryPolicy>` - execution parameter allowing to execute again the failed jobs
+ * - `joblog : Option<Arc<JobLog>>` - execution parameter allowing to record the executed jobs in a file
+ * - `skip : BTreeSet<usize>` - the sequence numbers (from 1) of the jobs which aren't executed, as they were in a previous execution
  * # Example
  * ```rust
- * use rust_parallel::core::jobmanager::JobManager;
+ * use rust_parallel::core::jobmanager::{ExecOptions, JobManager};
  * use rust_parallel::core::job::Job;
  * let mut jobmanager : JobManager = JobManager::new(String::from("/bin/bash"));
- * jobmanager.set_exec_env(Some(5), false, true, None, None); //5 threads, no "dry run", keep order
+ * jobmanager.set_options(ExecOptions::new().jobs(5).keep_order(true)).unwrap(); //5 jobs at a time, keep order
  * let args: Vec<String> = vec![
  *             String::from("echo"),
  *             String::from("Hello"),
  *             String::from("World"),
  *         ];
  * jobmanager.add_job(Job::new(args));
- * jobmanager.exec();
+ * jobmanager.exec().unwrap();
  * ```
  */
 pub struct JobManager {
     pub shell: String, //the shell used to launch jobs
     cmds: Vec<Job>,
-    nb_thread: Option<usize>,
+    source: Option<JobSource>,
+    nb_jobs: Option<usize>,
     dry_run: bool,
     keep_order: bool,
+    output: OutputMode,
+    halt: Option<HaltPolicy>,
+    timeout: Option<Timeout>,
+    retry: Option<RetryPolicy>,
+    joblog: Option<Arc<JobLog>>,
+    skip: BTreeSet<usize>,
     local_port: Option<usize>,
     remote_addr: Option<(String, usize)>,
-    request: String,
+    request: Vec<String>,
+    interrupt: Option<watch::Receiver<bool>>,
+    stdin: bool,
+    options: ExecOptions,
 }
 
 /***
@@ -50,12 +458,15 @@ impl fmt::Display for JobManager {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let _r = write!(
             f,
-            "JobManager with \n\t{:?} threads \n\tdry-run : {} \n\tkeep-order : {}",
-            self.nb_thread, self.dry_run, self.keep_order
+            "JobManager with \n\t{:?} jobs at a time \n\tdry-run : {} \n\tkeep-order : {}",
+            self.nb_jobs, self.dry_run, self.keep_order
         );
         for i in 0..self.cmds.len() {
             let _r = write!(f, "\n\t{}", self.cmds[i]);
         }
+        if self.source.is_some() {
+            let _r = write!(f, "\n\t(more jobs created from the input)");
+        }
         Ok(())
     }
 }
@@ -65,27 +476,47 @@ impl JobManager {
      * Return a new job manager with default values of the execution parameters.
      * # Attributs
      * - `cmds` - is initialized and empty
-     * - `nb_thread` - None
+     * - `source` - None
+     * - `nb_jobs` - the number of CPUs
      * - `dry_run` - false
      * - `keep_order` - false
+     * - `output` - Display
+     * - `halt` - None
+     * - `timeout` - None
+     * - `retry` - None
+     * - `joblog` - None
+     * - `skip` - is initialized and empty
+     * - `interrupt` - None
+     * - `stdin` - true
+     * - `options` - the default execution options
      */
     pub fn new(shell: String) -> JobManager {
         JobManager {
             shell: shell,
             cmds: vec![],
-            nb_thread: None,
+            source: None,
+            nb_jobs: Some(nb_cpus()),
             dry_run: false,
             keep_order: false,
+            output: OutputMode::Display,
+            halt: None,
+            timeout: None,
+            retry: None,
+            joblog: None,
+            skip: BTreeSet::new(),
             remote_addr: None,
             local_port: None,
-            request: String::new(),
+            request: Vec::new(),
+            interrupt: None,
+            stdin: true,
+            options: ExecOptions::new(),
         }
     }
 
     /**
-     * Set the request field with the given string
+     * Set the request field with the words of the command line, sent to the server by a client
      */
-    pub fn set_request(&mut self, request: String) {
+    pub fn set_request(&mut self, request: Vec<String>) {
         self.request = request;
     }
 
@@ -98,43 +529,116 @@ impl JobManager {
         self.cmds.push(job);
     }
 
+    /**
+     * Allows to add jobs that will only be created during the execution,
+     * after the jobs given by `add_job`.
+     * # Arguments
+     * - `source` - An iterator creating the jobs, it may block while waiting for its input
+     */
+    pub fn set_source(&mut self, source: JobSource) {
+        self.source = Some(source);
+    }
+
+    /**
+     * Allows to record each job in a joblog as soon as it ends.
+     * # Arguments
+     * - `joblog` - the joblog, None to record nothing
+     */
+    pub fn set_joblog(&mut self, joblog: Option<JobLog>) {
+        self.joblog = joblog.map(Arc::new);
+    }
+
+    /**
+     * Allows to resume a previous execution by skipping the jobs it already executed.
+     * # Arguments
+     * - `skip` - the sequence numbers of the jobs to skip, the first job given is 1
+     */
+    pub fn set_skip(&mut self, skip: BTreeSet<usize>) {
+        self.skip = skip;
+    }
+
+    /**
+     * Allows to stop the execution before its end, ex: when the program receives SIGINT.
+     * The running jobs are killed and no new job is started once the value is true,
+     * their outputs are the ones of the jobs which ended before.
+     * # Arguments
+     * - `interrupt` - the receiver of the value asking to stop
+     * # Additional information
+     * The job manager doesn't listen any signal itself : the jobs run in their own process group,
+     * so they don't receive the signals sent to the terminal and only the caller decides when they are killed.
+     * A remote execution can't be interrupted this way, the receiver is dropped when it starts.
+     */
+    pub fn set_interrupt(&mut self, interrupt: watch::Receiver<bool>) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /**
+     * Allows to forbid the input of the jobs to be read from the standard input (the values
+     * or the blocks of --pipe), ex: for a server whose standard input isn't the one of its clients.
+     * The command line must then give its values with ::: or ::::.
+     * # Arguments
+     * - `stdin` - true if the standard input can be read, by default
+     */
+    pub fn set_stdin(&mut self, stdin: bool) {
+        self.stdin = stdin;
+    }
+
+    /**
+     * Return true if the input of the jobs can be read from the standard input, see `set_stdin`.
+     */
+    pub fn stdin(&self) -> bool {
+        self.stdin
+    }
+
+    /**
+     * Return the execution options given by `set_options`, the default ones before,
+     * so some of them can be changed without changing the others.
+     */
+    pub fn options(&self) -> &ExecOptions {
+        &self.options
+    }
+
     /**
      * Allows to change the default values of the execution parameters.
      * # Arguments
-     * - `nb` - to change the number of thread
-     * - `d_r` - to change *dry run* value
-     * - `k_o` - to change *keep order* value
+     * - `options` - the execution options, checked before they are used
      * # Additional information
-     * `d_r` and `k_o` can take true|false values.
-     * `nb` can take Some(uzise)|None values but in case of Some(0) the value realy given to the manager is None
-     */
-    pub fn set_exec_env(
-        &mut self,
-        nb: Option<usize>,
-        d_r: bool,
-        k_o: bool,
-        src_port: Option<usize>,
-        dst_addr: Option<(String, usize)>,
-    ) {
-        self.nb_thread = match nb {
-            None | Some(0) => None,
-            Some(_) => nb,
+     * The job manager is unchanged if the options can't be used together.
+     */
+    pub fn set_options(&mut self, options: &ExecOptions) -> Result<(), OptionsError> {
+        options.validate()?;
+        self.nb_jobs = match options.jobs {
+            None => Some(nb_cpus()),
+            Some(0) => None,
+            Some(nb) => Some(nb),
         };
-        self.dry_run = d_r;
-        self.keep_order = k_o;
-        self.local_port = src_port;
-        self.remote_addr = dst_addr;
+        self.dry_run = options.dry_run;
+        self.keep_order = options.keep_order;
+        self.output = options.output;
+        self.halt = options.halt;
+        self.timeout = options.timeout;
+        self.retry = options.retry;
+        self.local_port = options.server;
+        self.remote_addr = options.client.clone();
+        self.options = options.clone();
+        Ok(())
     }
 
     /**
      * Allows to execute all the commands in according to the execution parameters.
      *
      * In case dry run is requested, then the other parameters are not very useful, we only display the commands.
+     * Return the output of each job executed, locally or by the server of the client, in the order they
+     * were displayed (none for a dry run or the server), or the error of the client or of the server.
      */
-    pub fn exec(mut self) -> Option<Vec<String>> {
+    pub fn exec(mut self) -> Result<Vec<JobOutput>, Error> {
+        if self.remote_addr.is_some() || self.local_port.is_some() {
+            // the caller knows the remote execution can't be interrupted once the receiver is dropped
+            self.interrupt = None;
+        }
         if self.dry_run {
-            self.dry_run();
-            return None;
+            self.dry_run(&mut io::stdout());
+            Ok(Vec::new())
         } else if let Some(ip_addr) = &self.remote_addr {
             // This case corresponds to the Client side of a remote execution
             let port_string = ip_addr.1.to_string();
@@ -142,40 +646,44 @@ impl JobManager {
             address.push(':');
             address.push_str(&port_string);
 
-
             // After we've collected all the information needed to launch
             // the connection, we can remove the remote execution arguments
             // from the request in order to send it to the Server
-            let mut tokens: Vec<&str> = self.request.split_whitespace().collect();
+            let mut tokens: Vec<String> = self.request.clone();
             let mut index: usize = 0;
+            // "--client ADDRESS PORT" or "--client=ADDRESS PORT"
+            let mut nb_words: usize = 3;
 
             for token in &tokens {
-                if token.eq_ignore_ascii_case("--client") {
+                let name = token.split('=').next().unwrap_or_default();
+                if name.eq_ignore_ascii_case("--client") {
+                    if name.len() < token.len() {
+                        nb_words = 2;
+                    }
                     break;
                 }
                 index += 1;
             }
-            tokens.remove(index);
-            tokens.remove(index);
-            tokens.remove(index);
-
-            let mut new_request: String = String::new();
-            for token in tokens {
-                new_request.push_str(token);
-                new_request.push(' ');
+            tokens.drain(index..(index + nb_words).min(tokens.len()));
+
+            // the words are sent separately so the server gets them as they were given
+            let new_request: String = tokens.join(&REQUEST_SEPARATOR.to_string());
+
+            let rt = Runtime::new()?;
+
+            let mut outputs = rt.block_on(async {
+                let mut client: ParallelClient = ParallelClient::new(address, new_request);
+                client.start_client().await
+            })?;
+            println!("Result of the request :");
+            // the outputs are written like for a local execution, the status is returned for the exit code
+            for output in &mut outputs {
+                JobManager::display(output);
+                output.stdout = Vec::new();
+                output.stderr = Vec::new();
             }
 
-            let rt = Runtime::new().unwrap();
-
-            rt.block_on(async {
-                let mut client: ParallelClient =
-                    ParallelClient::new(String::from(address), new_request);
-                let res = client.start_client().await;
-                println!("Result of the request :");
-                println!("{}", res.unwrap());
-            });
-
-            return None;
+            Ok(outputs)
         } else if let Some(port_number) = self.local_port {
             // This case corresponds to the Server side of a remote execution
             let port_string = port_number.to_string();
@@ -184,216 +692,425 @@ impl JobManager {
 
             let mut runtime_builder: Builder = Builder::new_multi_thread();
             runtime_builder.enable_all();
-            let runtime = runtime_builder.build().unwrap();
+            let runtime = runtime_builder.build()?;
 
             runtime.block_on(async {
-                let mut server: ParallelServer = ParallelServer::new(address).await;
+                let mut server: ParallelServer = ParallelServer::new(address).await?;
                 server.waiting_request().await;
-            });
+                Ok::<(), Error>(())
+            })?;
 
-            return None;
+            Ok(Vec::new())
         } else {
-            let messages = self.exec_all();
-            return Some(messages);
+            self.exec_all()
         }
     }
 
     /**
      * Private function.
      *
-     * Display the list of command.
+     * Display the list of command, quoted as a shell would execute them (see `Job::command_line`).
      */
-    fn dry_run(&mut self) {
-        for i in 0..self.cmds.len() {
-            println!("{}", self.cmds[i]);
+    fn dry_run(&mut self, out: &mut impl Write) {
+        let cmds = std::mem::take(&mut self.cmds);
+        let jobs = cmds
+            .into_iter()
+            .chain(self.source.take().into_iter().flatten());
+        for (index, job) in jobs.enumerate() {
+            if !self.skip.contains(&(index + 1)) {
+                let _ = writeln!(out, "{}", job.command_line());
+            }
         }
     }
 
     /**
      * Private function.
      *
-     * Execute the list of command (with the requested number of threads), gives them an order
-     * and asynchronously retrieve the standard output of the threads in order to display them
-     * (using the order if requested)
+     * Wait for the caller to ask the execution to stop (see `set_interrupt`) and return true.
+     * Return false at once if it can't ask it.
      */
-    fn exec_all(mut self) -> Vec<String> {
-        debug!("{} {:?}", process::id(), thread::current().id());
+    async fn interrupted(interrupt: &mut Option<watch::Receiver<bool>>) -> bool {
+        match interrupt {
+            Some(interrupt) => {
+                while !*interrupt.borrow() {
+                    if interrupt.changed().await.is_err() {
+                        return false;
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
 
+    /**
+     * Private function.
+     *
+     * Display the standard output and the standard error of a job (in this order) at once,
+     * so they aren't mixed with the ones of the other jobs.
+     */
+    fn display(output: &JobOutput) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&output.stdout);
+        let _ = stdout.flush();
+
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(&output.stderr);
+        let _ = stderr.flush();
+    }
 
+    /**
+     * Private function.
+     *
+     * Build the runtime in which the jobs are executed.
+     * Threads work as consumer producers, the number of jobs running at the same time
+     * doesn't depend on them as the jobs are waited asynchronously.
+     */
+    fn build_runtime() -> io::Result<Runtime> {
+        let mut runtime_builder: Builder = Builder::new_multi_thread();
+        runtime_builder.enable_all();
+        runtime_builder.build()
+    }
 
-        // Check if a runtime already exists
-        if let Err(_e) = Handle::try_current() {
+    /**
+     * Private function.
+     *
+     * Execute the list of command (with the requested number of jobs at a time) in a new runtime
+     * and return their output, or the error preventing their execution.
+     */
+    fn exec_all(self) -> Result<Vec<JobOutput>, Error> {
+        debug!("{} {:?}", process::id(), thread::current().id());
 
-        // Create a asynchronous tokio runtime with the given number of thread.
-        // Threads work as consumer producers
-            let mut runtime_builder: Builder = Builder::new_multi_thread();
-            runtime_builder.enable_all();
-            let runtime: Runtime = match self.nb_thread {
-                None => runtime_builder.build().unwrap(),
-                Some(n) => runtime_builder.worker_threads(n).build().unwrap(),
-            };
+        // Check if a runtime already exists
+        if Handle::try_current().is_err() {
+            JobManager::build_runtime()?.block_on(self.run())
+        } else {
+            // We can't block the thread of a running runtime (it happens on the server side),
+            // so the jobs are executed by another thread with its own runtime.
+            thread::spawn(move || JobManager::build_runtime()?.block_on(self.run()))
+                .join()
+                .map_err(|_| panicked("the thread executing the jobs"))?
+        }
+    }
 
-            let nb_cmd = self.cmds.len();
+    /**
+     * Private function.
+     *
+     * Execute the jobs, gives them an order and asynchronously retrieve the standard output and
+     * the standard error of the jobs in order to display them as soon as they end (using the order if requested).
+     * The jobs of the source are created while the first ones are already running.
+     */
+    async fn run(mut self) -> Result<Vec<JobOutput>, Error> {
+        debug!("start block_on");
 
-            // Run all command into the runtime previously created.
-            let result = futures::executor::block_on(runtime.spawn(async move {
-                debug!("{} {:?}", process::id(), thread::current().id());
-                debug!("start block_on");
-
-                // Allows to keep access to the different tasks given to the threads.
-                let mut tasks: Vec<JoinHandle<_>> = vec![];
-
-                // mpsc = multi producer single consumer
-                // allow to the main thread to retrieve the output of child threads
-                let (tx, mut rx) =
-                    mpsc::channel::<(i32, Result<process::Output, std::io::Error>)>(1);
-
-                // allows to keep the execution order
-                let mut order: i32 = 0;
-
-                // for each command/job
-                for mut cmd in self.cmds.drain(..) {
-                    // create new producer
-                    let tx_task = tx.clone();
-
-                    // gives a new task to the runtime which executes the command and get output asynchronoulsy
-                    let task = tokio::spawn(async move {
-                        let output = cmd.exec().await;
-                        tx_task.send((order, output)).await.unwrap();
-                    });
-                    tasks.push(task);
-                    order += 1;
+        // the number of jobs is only known when they aren't created from an input
+        let known_total = match self.source {
+            None => Some(self.cmds.len() - self.skip.range(1..=self.cmds.len()).count()),
+            Some(_) => None,
+        };
+        let started = Arc::new(AtomicUsize::new(0));
+        let started_dispatcher = started.clone();
+
+        let cmds = std::mem::take(&mut self.cmds);
+        let jobs = cmds
+            .into_iter()
+            .chain(self.source.take().into_iter().flatten());
+
+        // mpsc = multi producer single consumer
+        // allow to the main task to retrieve the output of the jobs
+        let (tx, mut rx) = mpsc::channel::<(usize, JobOutput)>(1);
+
+        // Each running job holds a slot, so no more than nb_jobs children are alive at a time
+        let slots = Arc::new(Slots::new(self.nb_jobs));
+        let slots_dispatcher = slots.clone();
+
+        // With keep order, a job takes a place in the reorder buffer until its output is displayed,
+        // so the outputs waiting for a slow job can't grow without limit
+        let window = if self.keep_order {
+            let size = REORDER_BUFFER_SIZE.max(self.nb_jobs.unwrap_or(0));
+            Some(Arc::new(Semaphore::new(size)))
+        } else {
+            None
+        };
+        let window_dispatcher = window.clone();
+
+        // Once the halt condition is reached, no new job is started
+        let halted = Arc::new(AtomicBool::new(false));
+        let halted_dispatcher = halted.clone();
+        // even if the dispatcher is waiting for the next job of the input
+        let (stop_tx, mut stop_rx) = watch::channel(false);
+        // and with "now" the running jobs are killed
+        let (kill_tx, kill_rx) = watch::channel(false);
+
+        // The time limit of the running jobs, relative to the runtime of the completed ones with a percentage
+        let (limit_tx, limit_rx) = watch::channel(match self.timeout {
+            Some(Timeout::Duration(duration)) => Some(duration),
+            _ => None,
+        });
+        // the runtimes of the completed jobs, sorted to find their median
+        let mut runtimes: Vec<Duration> = vec![];
+
+        // The jobs are killed when the caller interrupts the execution
+        let mut interrupt = self.interrupt.take();
+        let mut interrupted: bool = false;
+
+        let retry = self.retry;
+        let joblog = self.joblog.clone();
+        let skip = std::mem::take(&mut self.skip);
+
+        // The jobs are created in a thread of their own : creating the next job may have to wait
+        // for the input (--pipe or the lines of the standard input) until it is closed, while the
+        // execution must stop at once when it halts or is interrupted
+        let (job_tx, mut job_rx) = mpsc::channel::<Job>(1);
+        thread::spawn(move || {
+            for job in jobs {
+                // the dispatcher stopped
+                if job_tx.blocking_send(job).is_err() {
+                    break;
                 }
+            }
+        });
 
-                // allows to wait for the output of all commands and to store them
-                // either in the order of arrival or in the order of execution (if requested => keep order)
-                let mut counter: usize = 0;
-                let mut messages = vec![Default::default(); nb_cmd]; // create Vector with default value
-                while counter < nb_cmd {
-                    let (order, result) = rx.recv().await.unwrap();
-                    let message: String = match result {
-                        // if the command is correct
-                        Ok(output) => {
-                            // if the command was executed successfully
-                            if output.status.success() {
-                                String::from_utf8(output.stdout.clone()).unwrap()
-                            } else {
-                                String::from_utf8(output.stderr.clone()).unwrap()
-                            }
-                        }
-                        // the command is uncorrect
-                        Err(e) => {
-                            let mut msg = String::from(e.to_string());
-                            msg.push_str("\n");
-                            msg
-                        }
-                    };
-
-                    if self.keep_order {
-                        messages[order as usize] = message;
-                    } else {
-                        messages[counter] = message;
-                    }
-                    counter += 1;
+        // The dispatcher gives a new task to the runtime for each job,
+        // the task executes the command and get output asynchronously
+        let dispatcher = tokio::spawn(async move {
+            // Allows to keep access to the different tasks given to the threads.
+            let mut tasks: Vec<JoinHandle<_>> = vec![];
+
+            // allows to keep the execution order
+            let mut order: usize = 0;
+            // the number of the job in the input, it identifies the job in the joblog
+            let mut seq: usize = 0;
+
+            loop {
+                let mut job = tokio::select! {
+                    job = job_rx.recv() => match job {
+                        Some(job) => job,
+                        None => break,
+                    },
+                    _ = stop_rx.changed() => break,
+                };
+                seq += 1;
+                if skip.contains(&seq) {
+                    continue;
                 }
 
-                // display output message
-                for i in 0..messages.len() {
-                    print!("{}", messages[i]);
+                // the slots and the reorder buffer are closed when the execution halts
+                // wait for the output of the first jobs to be displayed if the reorder buffer is full
+                if let Some(window) = &window_dispatcher {
+                    match window.acquire().await {
+                        Ok(permit) => permit.forget(),
+                        Err(_) => break,
+                    }
                 }
 
-                future::join_all(tasks).await;
-
-                debug!("stop block_on");
-                return messages;
-            }));
-
-            return result.unwrap();
-        } else {
-
-            let nb_cmd = self.cmds.len();
-
-            // Run all command into the runtime previously created.
-            let res = tokio::task::spawn_blocking(move || {
-                debug!("{} {:?}", process::id(), thread::current().id());
-                debug!("start block_on");
-
-                // Allows to keep access to the different tasks given to the threads.
-                let mut tasks: Vec<JoinHandle<_>> = vec![];
-
-                // mpsc = multi producer single consumer
-                // allow to the main thread to retrieve the output of child threads
-                let (tx, mut rx) =
-                    mpsc::channel::<(i32, Result<process::Output, std::io::Error>)>(1);
-
-                // allows to keep the execution order
-                let mut order: i32 = 0;
-
-                // for each command/job
-                for mut cmd in self.cmds.drain(..) {
-                    // create new producer
-                    let tx_task = tx.clone();
-
-                    // gives a new task to the runtime which executes the command and get output asynchronoulsy
-                    let task = tokio::spawn(async move {
-                        let output = cmd.exec().await;
-                        tx_task.send((order, output)).await.unwrap();
-                    });
-                    tasks.push(task);
-                    order += 1;
+                // wait for a running job to end if the limit is reached
+                let mut slot = match slots_dispatcher.take().await {
+                    Some(slot) => slot,
+                    None => break,
+                };
+                if halted_dispatcher.load(Ordering::SeqCst) {
+                    break;
                 }
-                // allows to wait for the output of all commands and to store them
-                // either in the order of arrival or in the order of execution (if requested => keep order)
-                let mut counter: usize = 0;
-                let mut messages = vec![Default::default(); nb_cmd]; // create Vector with default value
-                while counter < nb_cmd {
-                    let (order, result) = futures::executor::block_on(rx.recv()).unwrap();
-                    let message: String = match result {
-                        // if the command is correct
-                        Ok(output) => {
-                            // if the command was executed successfully
-                            if output.status.success() {
-                                String::from_utf8(output.stdout.clone()).unwrap()
-                            } else {
-                                String::from_utf8(output.stderr.clone()).unwrap()
+                started_dispatcher.fetch_add(1, Ordering::SeqCst);
+
+                // create new producer
+                let tx_task = tx.clone();
+                let mut kill = kill_rx.clone();
+                let time_limit = limit_rx.clone();
+                let slots_task = slots_dispatcher.clone();
+                let halted_task = halted_dispatcher.clone();
+                let joblog_task = joblog.clone();
+                let task = tokio::spawn(async move {
+                    // the output of the last attempt, and true if the job was killed by the halt or an interrupt
+                    let (output, killed) = loop {
+                        job.set_slot(slot.number);
+                        let attempt = job.attempt();
+                        let start_time = SystemTime::now();
+                        let start = Instant::now();
+                        let output = tokio::select! {
+                            output = job.run(time_limit.clone()) => Some(output),
+                            // dropping the execution kills the command
+                            _ = kill.changed() => None,
+                        };
+                        let output = match output {
+                            Some(output) => output,
+                            None => {
+                                break (
+                                    JobOutput::killed(start_time, start.elapsed(), attempt),
+                                    true,
+                                )
                             }
+                        };
+                        drop(slot);
+
+                        let retry = match retry {
+                            Some(retry) if !output.succeeded() && retry.retry(attempt) => retry,
+                            _ => break (output, false),
+                        };
+                        debug!("<{}> failed at attempt {}, retried", job, attempt);
+
+                        // the job is queued again, its slot is given to the other jobs while it waits
+                        let killed = tokio::select! {
+                            _ = time::sleep(retry.delay(attempt)) => false,
+                            _ = kill.changed() => true,
+                        };
+                        if killed {
+                            // the failed attempt is the last one
+                            break (output, true);
                         }
-                        // the command is uncorrect
-                        Err(e) => {
-                            let mut msg = String::from(e.to_string());
-                            msg.push_str("\n");
-                            msg
+                        slot = match slots_task.take().await {
+                            Some(slot) => slot,
+                            // the execution halted, the last output is kept
+                            None => break (output, false),
+                        };
+                        if halted_task.load(Ordering::SeqCst) {
+                            break (output, false);
                         }
+                        job.set_attempt(attempt + 1);
                     };
+                    // the killed jobs are recorded too, so a resumed execution executes them again
+                    if let Some(joblog) = joblog_task {
+                        joblog.record(seq, &job.command_line(), job.input_len(), &output);
+                    }
+                    if killed {
+                        return;
+                    }
+                    // the outputs are received until all the jobs end, unless the receiver panicked
+                    if tx_task.send((order, output)).await.is_err() {
+                        debug!("the output of <{}> is lost", job);
+                    }
+                });
+                tasks.push(task);
+                order += 1;
+            }
 
-                    if self.keep_order {
-                        messages[order as usize] = message;
-                    } else {
-                        messages[counter] = message;
+            future::join_all(tasks).await;
+        });
+
+        // allows to wait for the output of all commands and to display them
+        // either in the order of arrival or in the order of execution (if requested => keep order)
+        let output_mode = self.output;
+        // once displayed, only the status of a job is kept, what it wrote may be big
+        let display = |mut output: JobOutput| {
+            if output_mode == OutputMode::Display {
+                JobManager::display(&output);
+                output.stdout = Vec::new();
+                output.stderr = Vec::new();
+            }
+            output
+        };
+        let mut messages: Vec<JobOutput> = vec![];
+        // with keep order, the outputs waiting for the ones of the previous jobs
+        let mut waiting: BTreeMap<usize, JobOutput> = BTreeMap::new();
+        let mut next_order: usize = 0;
+        let mut nb_succeeded: usize = 0;
+        let mut nb_failed: usize = 0;
+        let mut killing: bool = false;
+        loop {
+            let (order, output) = tokio::select! {
+                message = rx.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                true = Self::interrupted(&mut interrupt), if !interrupted => {
+                    debug!("interrupted, the running jobs are killed");
+                    interrupted = true;
+                    halted.store(true, Ordering::SeqCst);
+                    let _ = stop_tx.send(true);
+                    slots.close();
+                    if let Some(window) = &window {
+                        window.close();
                     }
-                    counter += 1;
+                    let _ = kill_tx.send(true);
+                    killing = true;
+                    continue;
                 }
+            };
+            if killing {
+                // the jobs ending while they are killed aren't displayed
+                continue;
+            }
 
+            if let Some(Timeout::Percent(percent)) = self.timeout {
+                if output.status.is_some() && !output.timed_out {
+                    let index = runtimes
+                        .binary_search(&output.runtime)
+                        .unwrap_or_else(|i| i);
+                    runtimes.insert(index, output.runtime);
+                    let median = runtimes[runtimes.len() / 2];
+                    let limit = u32::try_from(percent)
+                        .ok()
+                        .and_then(|percent| median.checked_mul(percent))
+                        .map(|limit| limit / 100);
+                    let _ = limit_tx.send(limit);
+                }
+            }
 
-                // display output message
-                for i in 0..messages.len() {
-                    print!("{}", messages[i]);
+            if output.succeeded() {
+                nb_succeeded += 1;
+            } else {
+                nb_failed += 1;
+            }
+
+            match &window {
+                None => messages.push(display(output)),
+                Some(window) => {
+                    // the outputs are released once all the previous jobs are displayed
+                    waiting.insert(order, output);
+                    while let Some(output) = waiting.remove(&next_order) {
+                        messages.push(display(output));
+                        window.add_permits(1);
+                        next_order += 1;
+                    }
                 }
+            }
 
-                futures::executor::block_on(future::join_all(tasks));
+            if let Some(halt) = self.halt {
+                let nb_jobs = known_total.unwrap_or_else(|| started.load(Ordering::SeqCst));
+                if !halted.load(Ordering::SeqCst) && halt.reached(nb_succeeded, nb_failed, nb_jobs)
+                {
+                    debug!("halt condition reached, {:?}", halt);
+                    halted.store(true, Ordering::SeqCst);
+                    let _ = stop_tx.send(true);
+                    slots.close();
+                    if let Some(window) = &window {
+                        window.close();
+                    }
 
-                debug!("stop block_on");
-                return messages;
-            });
-            // Now we wait for the task previously created to end
-            return futures::executor::block_on(res).unwrap();
+                    if halt.when == HaltWhen::Now {
+                        // the outputs waiting for a killed job are displayed now
+                        for (_, output) in std::mem::take(&mut waiting) {
+                            messages.push(display(output));
+                        }
+                        let _ = kill_tx.send(true);
+                        killing = true;
+                    }
+                }
+            }
         }
+        dispatcher
+            .await
+            .map_err(|_| panicked("the dispatcher of the jobs"))?;
+
+        debug!("stop block_on");
+        Ok(messages)
     }
 }
 
+/**
+ * Private function.
+ *
+ * Return the error of a thread or a task which panicked while executing the jobs.
+ */
+fn panicked(what: &str) -> Error {
+    Error::Io(io::Error::other(format!("{} panicked", what)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::job::SLOT_MARK;
     use std::thread::sleep;
     use std::time::Duration;
 
@@ -401,11 +1118,45 @@ mod tests {
 
     fn init_jm(nb: Option<usize>, d_r: bool, k_o: bool) -> JobManager {
         let mut jobmanager = JobManager::new(String::from("/bin/bash"));
-        jobmanager.set_exec_env(nb, d_r, k_o, None, None);
+        jobmanager.set_options(&options(nb, d_r, k_o)).unwrap();
 
         jobmanager
     }
 
+    /// The jobs executing each command by bash.
+    fn jobs<S: AsRef<str>>(commands: &[S]) -> Vec<Job> {
+        commands
+            .iter()
+            .map(|command| {
+                Job::new(vec![
+                    String::from("/bin/bash"),
+                    String::from("-c"),
+                    String::from(command.as_ref()),
+                ])
+            })
+            .collect()
+    }
+
+    /// A file of the temporary directory used by a single test, even when the tests of
+    /// several processes run at the same time.
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rust_parallel_{}_{}", process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn options(nb: Option<usize>, d_r: bool, k_o: bool) -> ExecOptions {
+        let mut options = ExecOptions::new();
+        // the outputs are kept so the tests can check them
+        options
+            .dry_run(d_r)
+            .keep_order(k_o)
+            .output(OutputMode::Collect);
+        if let Some(nb) = nb {
+            options.jobs(nb);
+        }
+        options
+    }
+
     #[test]
     fn test_echo1() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -419,7 +1170,7 @@ mod tests {
         ];
         jobmanager.add_job(Job::new(args));
 
-        jobmanager.exec();
+        jobmanager.exec().unwrap();
     }
 
     #[test]
@@ -445,7 +1196,7 @@ mod tests {
         ];
         jobmanager.add_job(Job::new(args));
 
-        jobmanager.exec();
+        jobmanager.exec().unwrap();
     }
 
     fn init(nb_thread: Option<usize>) -> (JobManager, Runtime) {
@@ -578,6 +1329,476 @@ mod tests {
         let args: Vec<String> = vec![String::from("unknown")];
         jobmanager.add_job(Job::new(args));
 
-        jobmanager.exec();
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(exit_code(&messages), 1);
+    }
+
+    #[test]
+    fn test_jobs_limit() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // 4 jobs of 1 second, 2 at a time
+        let mut jobmanager = init_jm(Some(2), false, false);
+        for _ in 0..4 {
+            let args: Vec<String> = vec![String::from("sleep"), String::from("1")];
+            jobmanager.add_job(Job::new(args));
+        }
+
+        let start = std::time::Instant::now();
+        jobmanager.exec().unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(2));
+        assert!(elapsed < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_jobs_max() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // a greater number of jobs is reduced to the maximum, with keep order too
+        let mut jobmanager = init_jm(Some(usize::MAX), false, true);
+        for job in jobs(&["true", "true"]) {
+            jobmanager.add_job(job);
+        }
+        assert_eq!(jobmanager.exec().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_keep_order() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut jobmanager = init_jm(NB_THREAD, false, true);
+        let commands: Vec<String> = (0..3)
+            .map(|i| format!("sleep 0.{}; echo {}", 3 - i, i))
+            .collect();
+        for job in jobs(&commands) {
+            jobmanager.add_job(job);
+        }
+
+        let messages = jobmanager.exec().unwrap();
+        let stdouts: Vec<&[u8]> = messages.iter().map(|m| m.stdout.as_slice()).collect();
+        assert_eq!(stdouts, vec![b"0\n", b"1\n", b"2\n"]);
+    }
+
+    #[test]
+    fn test_both_outputs() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the standard output of a failing job isn't lost
+        let mut jobmanager = init_jm(NB_THREAD, false, true);
+        for job in jobs(&["echo out; echo err >&2; exit 1"]) {
+            jobmanager.add_job(job);
+        }
+
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(messages[0].stdout, b"out\n");
+        assert_eq!(messages[0].stderr, b"err\n");
+        assert!(!messages[0].succeeded());
+    }
+
+    #[test]
+    fn test_binary_output() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // an output which isn't valid UTF-8 must not panic
+        let mut jobmanager = init_jm(NB_THREAD, false, false);
+        let args: Vec<String> = vec![String::from("printf"), String::from("\\377\\000\\376")];
+        jobmanager.add_job(Job::new(args));
+
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(messages[0].stdout, vec![0xff, 0x00, 0xfe]);
+    }
+
+    #[test]
+    fn test_halt() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the first failure stops everything, the running job is killed
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(Some(2), false, false).halt(HaltPolicy {
+                when: HaltWhen::Now,
+                on_success: false,
+                limit: HaltLimit::Count(1),
+            }))
+            .unwrap();
+        for job in jobs(&["sleep 5", "false", "echo never"]) {
+            jobmanager.add_job(job);
+        }
+
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(exit_code(&messages), 1);
+    }
+
+    #[test]
+    fn test_halt_waiting_input() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the execution stops without waiting for the next job of the input
+        let waiting_input = || {
+            let next = std::iter::from_fn(|| {
+                thread::sleep(Duration::from_secs(5));
+                None
+            });
+            Box::new(jobs(&["false"]).into_iter().chain(next))
+        };
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(Some(2), false, false).halt(HaltPolicy {
+                when: HaltWhen::Soon,
+                on_success: false,
+                limit: HaltLimit::Count(1),
+            }))
+            .unwrap();
+        jobmanager.set_source(waiting_input());
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(exit_code(&messages), 1);
+
+        // the same when it is interrupted
+        let mut jobmanager = init_jm(Some(2), false, false);
+        jobmanager.set_source(waiting_input());
+        let (interrupt, receiver) = watch::channel(false);
+        jobmanager.set_interrupt(receiver);
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            let _ = interrupt.send(true);
+        });
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(messages.len(), 1);
+        interrupter.join().unwrap();
+    }
+
+    #[test]
+    fn test_halt_joblog() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the job killed by the halt is recorded with the signal which killed it
+        let path = temp_path("test_halt_joblog.tsv");
+        let path = path.as_str();
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(Some(2), false, false).halt(HaltPolicy {
+                when: HaltWhen::Now,
+                on_success: false,
+                limit: HaltLimit::Count(1),
+            }))
+            .unwrap();
+        jobmanager.set_joblog(Some(JobLog::create(path).unwrap()));
+        for job in jobs(&["sleep 5", "sleep 0.2; false", "echo never"]) {
+            jobmanager.add_job(job);
+        }
+        jobmanager.exec().unwrap();
+
+        let joblog = std::fs::read_to_string(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut lines: Vec<Vec<&str>> = joblog
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').collect())
+            .collect();
+        lines.sort();
+        // the third job may start in the slot of the failed one before the halt
+        assert!(lines.len() >= 2);
+        assert_eq!(&lines[0][6..], &["-1", "9", "sleep 5"]);
+        assert_eq!(&lines[1][6..], &["1", "0", "sleep 0.2; false"]);
+    }
+
+    #[test]
+    fn test_halt_reached() {
+        let halt = |limit: HaltLimit| HaltPolicy {
+            when: HaltWhen::Soon,
+            on_success: false,
+            limit,
+        };
+        assert!(halt(HaltLimit::Count(2)).reached(5, 2, 10));
+        assert!(!halt(HaltLimit::Count(2)).reached(5, 1, 10));
+        assert!(halt(HaltLimit::Percent(20)).reached(0, 2, 10));
+        assert!(!halt(HaltLimit::Percent(20)).reached(0, 1, 10));
+        // the big numbers don't overflow
+        assert!(!halt(HaltLimit::Percent(usize::MAX)).reached(0, 1, 10));
+        assert!(halt(HaltLimit::Percent(1)).reached(0, usize::MAX, usize::MAX));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(
+                options(NB_THREAD, false, true)
+                    .timeout(Timeout::Duration(Duration::from_millis(500))),
+            )
+            .unwrap();
+        // the sleep is a child of the shell, it is killed with it
+        // the last job ignores TERM so it is killed after the grace period
+        for job in jobs(&["echo fast", "sleep 5; echo late", "trap '' TERM; sleep 5"]) {
+            jobmanager.add_job(job);
+        }
+
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].stdout, b"fast\n".to_vec());
+        assert!(!messages[0].timed_out);
+        assert!(messages[1].timed_out && messages[1].stdout.is_empty());
+        assert!(messages[2].timed_out);
+        assert_eq!(exit_code(&messages), 2);
+    }
+
+    #[test]
+    fn test_timeout_percent() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the last job is stopped once it runs twice as long as the first ones
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(Some(1), false, false).timeout(Timeout::Percent(200)))
+            .unwrap();
+        for duration in &["0.1", "0.1", "0.1", "5"] {
+            let args: Vec<String> = vec![String::from("sleep"), String::from(*duration)];
+            jobmanager.add_job(Job::new(args));
+        }
+
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            messages
+                .iter()
+                .map(|output| output.timed_out)
+                .collect::<Vec<bool>>(),
+            vec![false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_dry_run() {
+        // the values keep their boundaries in the displayed commands
+        let mut jobmanager = init_jm(NB_THREAD, true, false);
+        jobmanager.add_job(Job::new(vec![String::from("echo"), String::from("a b")]));
+        jobmanager.add_job(Job::new(vec![String::from("touch"), String::from("it's")]));
+        for job in jobs(&["echo 'c d'"]) {
+            jobmanager.add_job(job);
+        }
+        let mut out = Vec::new();
+        jobmanager.dry_run(&mut out);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "echo 'a b'\ntouch 'it'\\''s'\necho 'c d'\n"
+        );
+    }
+
+    #[test]
+    fn test_output_mode() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the collected outputs are returned with the status of the jobs
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(NB_THREAD, false, true).output(OutputMode::Collect))
+            .unwrap();
+        for job in jobs(&["echo a", "echo b >&2; false"]) {
+            jobmanager.add_job(job);
+        }
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].stdout, b"a\n".to_vec());
+        assert_eq!(messages[1].stderr, b"b\n".to_vec());
+
+        // the displayed ones are dropped, only the status is kept
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(NB_THREAD, false, true).output(OutputMode::Display))
+            .unwrap();
+        for job in jobs(&["echo a", "echo b >&2; false"]) {
+            jobmanager.add_job(job);
+        }
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|m| m.stdout.is_empty() && m.stderr.is_empty()));
+        assert!(messages[0].succeeded() && !messages[1].succeeded());
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // the running jobs are killed, the next ones aren't started
+        let mut jobmanager = init_jm(Some(1), false, false);
+        for job in jobs(&["echo first", "sleep 5; echo late", "echo never"]) {
+            jobmanager.add_job(job);
+        }
+        let (sender, receiver) = watch::channel(false);
+        jobmanager.set_interrupt(receiver);
+        let interrupter = thread::spawn(move || {
+            sleep(Duration::from_millis(500));
+            sender.send(true).unwrap();
+        });
+
+        let start = std::time::Instant::now();
+        let messages = jobmanager.exec().unwrap();
+        interrupter.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].stdout, b"first\n".to_vec());
+    }
+
+    #[test]
+    fn test_options() {
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(ExecOptions::new().jobs(0).dry_run(true))
+            .unwrap();
+        assert_eq!(jobmanager.nb_jobs, None);
+        assert!(jobmanager.dry_run && !jobmanager.keep_order);
+
+        // the job manager is unchanged by conflicting options
+        let mut options = ExecOptions::new();
+        options.jobs(3).server(8888).client("127.0.0.1", 8888);
+        assert_eq!(options.validate(), Err(OptionsError::ServerAndClient));
+        assert_eq!(
+            jobmanager.set_options(&options),
+            Err(OptionsError::ServerAndClient)
+        );
+        assert_eq!(jobmanager.nb_jobs, None);
+        assert_eq!(jobmanager.local_port, None);
+
+        // the options are kept so one of them can be changed afterwards
+        let mut options = jobmanager.options().clone();
+        assert!(options.dry_run);
+        options.output(OutputMode::Collect);
+        jobmanager.set_options(&options).unwrap();
+        assert_eq!(jobmanager.options(), &options);
+        assert!(jobmanager.dry_run && jobmanager.output == OutputMode::Collect);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let mut retry = RetryPolicy {
+            retries: 3,
+            delay: Duration::from_secs(1),
+            backoff: false,
+        };
+        assert!(retry.retry(3) && !retry.retry(4));
+        assert_eq!(retry.delay(3), Duration::from_secs(1));
+        retry.backoff = true;
+        assert_eq!(retry.delay(1), Duration::from_secs(1));
+        assert_eq!(retry.delay(3), Duration::from_secs(4));
+        assert_eq!(retry.delay(1000), Duration::MAX);
+    }
+
+    #[test]
+    fn test_retries() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut jobmanager = JobManager::new(String::from("/bin/bash"));
+        jobmanager
+            .set_options(options(NB_THREAD, false, true).retries(RetryPolicy {
+                retries: 3,
+                delay: Duration::from_millis(10),
+                backoff: true,
+            }))
+            .unwrap();
+        // the first job succeeds at its third attempt, the second one always fails
+        for job in jobs(&[
+            "echo $PARALLEL_ATTEMPT; test $PARALLEL_ATTEMPT -ge 3",
+            "false",
+        ]) {
+            jobmanager.add_job(job);
+        }
+
+        let messages = jobmanager.exec().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].succeeded());
+        assert_eq!(messages[0].attempt, 3);
+        assert_eq!(messages[0].stdout, b"3\n".to_vec());
+        assert_eq!(messages[1].attempt, 4);
+        assert_eq!(exit_code(&messages), 1);
+    }
+
+    #[test]
+    fn test_joblog() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let path = temp_path("test_joblog.tsv");
+        let path = path.as_str();
+        let mut jobmanager = init_jm(NB_THREAD, false, false);
+        jobmanager.set_joblog(Some(JobLog::create(path).unwrap()));
+        for job in jobs(&["exit 0", "exit 3", "kill $$"]) {
+            jobmanager.add_job(job);
+        }
+        jobmanager.exec().unwrap();
+
+        // the lines are written in the order the jobs ended
+        let joblog = std::fs::read_to_string(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut lines: Vec<Vec<&str>> = joblog
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').collect())
+            .collect();
+        lines.sort();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0][0], "1");
+        assert_eq!(&lines[0][6..], &["0", "0", "exit 0"]);
+        assert_eq!(&lines[1][6..], &["3", "0", "exit 3"]);
+        assert_eq!(&lines[2][6..], &["-1", "15", "kill $$"]);
+    }
+
+    #[test]
+    fn test_skip() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let path = temp_path("test_skip.tsv");
+        let path = path.as_str();
+        let mut jobmanager = init_jm(NB_THREAD, false, true);
+        jobmanager.set_joblog(Some(JobLog::create(path).unwrap()));
+        jobmanager.set_skip(vec![1, 3].into_iter().collect());
+        for value in &["1", "2", "3", "4"] {
+            let args: Vec<String> = vec![String::from("echo"), String::from(*value)];
+            jobmanager.add_job(Job::new(args));
+        }
+
+        let messages = jobmanager.exec().unwrap();
+        let joblog = std::fs::read_to_string(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        let stdouts: Vec<Vec<u8>> = messages.into_iter().map(|output| output.stdout).collect();
+        assert_eq!(stdouts, vec![b"2\n".to_vec(), b"4\n".to_vec()]);
+        // the skipped jobs keep their sequence number
+        let mut seqs: Vec<&str> = joblog
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').next().unwrap())
+            .collect();
+        seqs.sort();
+        assert_eq!(seqs, vec!["2", "4"]);
+    }
+
+    #[test]
+    fn test_slots() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // two jobs at a time share the slots 1 and 2
+        let mut jobmanager = init_jm(Some(2), false, true);
+        for job in jobs(&vec![format!("sleep 0.1; echo {}", SLOT_MARK); 6]) {
+            jobmanager.add_job(job);
+        }
+
+        let messages = jobmanager.exec().unwrap();
+        for output in &messages {
+            assert!(output.stdout == b"1\n" || output.stdout == b"2\n");
+        }
+        assert_ne!(messages[0].stdout, messages[1].stdout);
     }
 }
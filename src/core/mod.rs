@@ -1,4 +1,8 @@
+pub mod diagnostic;
+pub mod expression;
+pub mod input;
 pub mod interpreter;
 pub mod job;
+pub mod joblog;
 pub mod jobmanager;
 pub mod parser;
@@ -1,18 +1,545 @@
 extern crate pest;
 
-use pest::error::Error;
+use crate::core::diagnostic::{closest, Diagnostic};
+use crate::core::job::shell_quote;
+use pest::error::InputLocation;
 use pest::iterators::Pairs;
 use pest::Parser;
+use std::fmt;
 
 #[derive(Parser)]
 #[grammar = "core/parallel.pest"]
 pub struct ParallelParser;
 
-pub fn parse(raw_string: &str) -> Result<Pairs<Rule>, Error<Rule>> {
-    let parse_result = ParallelParser::parse(Rule::main, raw_string);
-    let inputs = match parse_result {
-        Ok(pairs) => pairs,
-        Err(error) => return Err(error),
+/// The options of the grammar with the number of values following them.
+const OPTIONS: &[(&str, usize)] = &[
+    ("--dry-run", 0),
+    ("--keep-order", 0),
+    ("--pipe", 0),
+    ("--block", 1),
+    ("--arg-file", 1),
+    ("-a", 1),
+    ("--link", 0),
+    ("--halt", 1),
+    ("--timeout", 1),
+    ("--retries", 1),
+    ("--retry-delay", 1),
+    ("--retry-backoff", 0),
+    ("--joblog", 1),
+    ("--resume-failed", 0),
+    ("--resume", 0),
+    ("--retry-failed", 0),
+    ("--rpl", 1),
+    ("--quote", 0),
+    ("-q", 0),
+    ("--no-shell", 0),
+    ("--shell", 0),
+    ("--jobs", 1),
+    ("-j", 1),
+    ("--server", 1),
+    ("--client", 2),
+    ("--help", 0),
+];
+
+/// The errors of the command line found by the parser, with the words where they have been found :
+/// the words of the command line (argv) are checked before the request is parsed by the grammar.
+#[derive(Debug)]
+pub enum ParseError {
+    UnknownOption(Diagnostic),
+    MissingValue(Diagnostic),
+    UnexpectedValue(Diagnostic),
+    InvalidValue(Diagnostic),
+    Syntax(Diagnostic),
+}
+
+impl ParseError {
+    /// The description of the error.
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            ParseError::UnknownOption(diagnostic)
+            | ParseError::MissingValue(diagnostic)
+            | ParseError::UnexpectedValue(diagnostic)
+            | ParseError::InvalidValue(diagnostic)
+            | ParseError::Syntax(diagnostic) => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagnostic())
+    }
+}
+
+/**
+ * Return the request read by the grammar for the words of the command line (argv), so the boundaries
+ * of the words are kept :
+ * - the options and the command are joined with spaces, the command is written as in the shell
+ *   (ex: "grep 'a b' {}" or grep a b), unless it is executed without a shell (--no-shell) or its
+ *   words are quoted (--quote) : then each word of the command is kept as a single word
+ * - the options can be given in any order before the command, the long ones as "--option=value" too
+ *   and the short ones as "-j4", and "--" ends them so the command can start with a dash ; the words
+ *   after the command are its own, even when they look like options (ex: make -j 4)
+ * - the values of the options are quoted if they contain whitespaces or quotes, or are empty
+ * - the input values are quoted unless they are made of safe characters only, so a value like
+ *   "a b", "" or ":::x" is a single value
+ *
+ * The errors of the options are found here, their spans are in the words joined with spaces.
+ * # Arguments
+ * - `tokens` - the words of the command line, without the program name
+ */
+pub fn request(tokens: &[String]) -> Result<String, ParseError> {
+    let line = tokens.join(" ");
+    // the span of each word in the line
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for token in tokens {
+        spans.push((start, start + token.len()));
+        start += token.len() + 1;
+    }
+    let diagnostic = |message: String, first: usize, last: usize| {
+        Diagnostic::new(message)
+            .at(spans[first].0, spans[last].1)
+            .in_line(&line)
     };
-    return Ok(inputs);
+
+    let mut words: Vec<String> = Vec::new();
+    let mut tokens = tokens.iter().enumerate();
+    // the last of --shell and --no-shell wins, like in the interpreter
+    let mut no_shell = false;
+    let mut quote = false;
+
+    // the options come first, each one followed by its values
+    let mut command = None;
+    while let Some((index, token)) = tokens.next() {
+        if token == "--" {
+            // the next word is the command even if it looks like an option,
+            // so its program is quoted to be read as the command by the grammar
+            command = tokens.next().map(|(_, word)| {
+                // the words quoted below are never read as options
+                if word.starts_with('-') && !no_shell && !quote {
+                    let end = word.find(char::is_whitespace).unwrap_or(word.len());
+                    format!("{}{}", quote_always(&word[..end]), &word[end..])
+                } else {
+                    word.clone()
+                }
+            });
+            break;
+        }
+
+        let (name, value) = match token.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => match token.get(..2).and_then(find_option) {
+                // a short option can be followed by its value in the same word (ex: "-j4")
+                Some((option, nb_values))
+                    if option.len() == 2 && nb_values > 0 && token.len() > 2 =>
+                {
+                    (&token[..2], Some(&token[2..]))
+                }
+                _ => (token.as_str(), None),
+            },
+        };
+        let (option, nb_values) = match find_option(name) {
+            Some(option) => option,
+            // a command starting with a dash is given after "--"
+            None if name.starts_with('-') && name.len() > 1 => {
+                let names: Vec<&str> = OPTIONS.iter().map(|(option, _)| *option).collect();
+                let suggestion = if name.starts_with("--") {
+                    closest(name, &names)
+                } else {
+                    // the short options only differ by their case (ex: "-J" for "-j")
+                    names
+                        .iter()
+                        .find(|option| option.eq_ignore_ascii_case(name))
+                        .copied()
+                };
+                let error = diagnostic(format!("unknown option {}", name), index, index);
+                return Err(ParseError::UnknownOption(match suggestion {
+                    Some(option) => error.suggest(option),
+                    None => error,
+                }));
+            }
+            None => {
+                command = Some(token.clone());
+                break;
+            }
+        };
+        match option {
+            "--no-shell" => no_shell = true,
+            "--shell" => no_shell = false,
+            "--quote" | "-q" => quote = true,
+            _ => (),
+        }
+
+        let mut option_words = vec![String::from(option)];
+        let mut last = index;
+        let mut nb_values = nb_values;
+        if let Some(value) = value {
+            // with "--option=value" the first value is in the same word
+            if nb_values == 0 {
+                return Err(ParseError::UnexpectedValue(diagnostic(
+                    format!("the option {} doesn't take any value", option),
+                    index,
+                    index,
+                )));
+            }
+            option_words.push(quote_if_needed(value));
+            nb_values -= 1;
+        }
+        for _ in 0..nb_values {
+            match tokens.next() {
+                Some((index, value)) => {
+                    option_words.push(quote_if_needed(value));
+                    last = index;
+                }
+                None => {
+                    return Err(ParseError::MissingValue(diagnostic(
+                        format!("missing value for the option {}", option),
+                        index,
+                        index,
+                    )))
+                }
+            }
+        }
+
+        // the values must be read by the rule of the option, or the option would be read as the command
+        let option_text = option_words.join(" ");
+        let valid = ParallelParser::parse(Rule::options, &option_text)
+            .is_ok_and(|pairs| pairs.as_str().len() == option_text.len());
+        if !valid {
+            let first = if value.is_some() { index } else { index + 1 };
+            return Err(ParseError::InvalidValue(diagnostic(
+                format!("invalid value for the option {}", option),
+                first,
+                last,
+            )));
+        }
+        words.extend(option_words);
+    }
+
+    // then the command and the input values after the first separator
+    let mut values = false;
+    for token in command.iter().chain(tokens.map(|(_, token)| token)) {
+        let separator = [":::", ":::+", "::::", "::::+"].contains(&token.as_str());
+        if values && !separator {
+            words.push(quote_value(token));
+        } else if !values && !separator && (no_shell || quote) {
+            words.push(quote_word(token));
+        } else {
+            words.push(token.clone());
+        }
+        values |= separator;
+    }
+    Ok(words.join(" "))
+}
+
+/**
+ * Parse the request of the command line (see `request`), the syntax errors are described
+ * with their span in the request.
+ * # Arguments
+ * - `request` - the request read by the grammar
+ */
+pub fn parse(request: &str) -> Result<Pairs<'_, Rule>, ParseError> {
+    ParallelParser::parse(Rule::main, request).map_err(|error| {
+        let (start, end) = match error.location {
+            InputLocation::Pos(position) => (position, position),
+            InputLocation::Span(span) => span,
+        };
+        let rest = &request[start..];
+        let message = if rest.starts_with('\'') || rest.starts_with('"') {
+            String::from("unterminated quote")
+        } else if rest.is_empty() {
+            String::from("unexpected end of the command line")
+        } else {
+            String::from("unexpected word")
+        };
+        // the span covers the offending word at least
+        let word = rest.find(' ').unwrap_or(rest.len());
+        ParseError::Syntax(
+            Diagnostic::new(message)
+                .at(start, end.max(start + word))
+                .in_line(request),
+        )
+    })
+}
+
+/**
+ * Private function.
+ *
+ * Return the option of the grammar with this name and its number of values,
+ * the case of the long options is ignored but not the one of the short options (ex: "-j").
+ */
+fn find_option(name: &str) -> Option<(&'static str, usize)> {
+    OPTIONS
+        .iter()
+        .find(|(option, _)| {
+            if option.starts_with("--") {
+                option.eq_ignore_ascii_case(name)
+            } else {
+                *option == name
+            }
+        })
+        .copied()
+}
+
+/**
+ * Private function.
+ *
+ * Return the input value quoted for the shell, and always quoted if it starts like a separator
+ * (ex: ":::x").
+ */
+fn quote_value(value: &str) -> String {
+    let quoted = shell_quote(value);
+    if quoted.starts_with(":::") {
+        quote_always(value)
+    } else {
+        quoted
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Return the word quoted for the shell even if it is made of safe characters only,
+ * so the grammar reads it as a string (ex: "'--dry-run'" or "':::x'").
+ */
+fn quote_always(word: &str) -> String {
+    let quoted = shell_quote(word);
+    if quoted.starts_with('\'') {
+        quoted
+    } else {
+        format!("'{}'", quoted)
+    }
+}
+
+/**
+ * Private function.
+ *
+ * Return the word of the command quoted so it is read as a single word, un-quoted as it is (ex: "my file"
+ * or "%s\n"). Its targets aren't quoted so they are still read as targets (ex: "'x'{}'.txt'"), unless
+ * they contain characters which would split the word (ex: "{a b}").
+ */
+fn quote_word(word: &str) -> String {
+    let mut quoted = String::new();
+    let mut text = String::new();
+    let mut rest = word;
+    while let Some(open_braces) = rest.find('{') {
+        text.push_str(&rest[..open_braces]);
+        rest = &rest[open_braces..];
+        // an expression ends at the first "=}", the other targets at the first "}"
+        let end = match rest.strip_prefix("{=") {
+            Some(source) => source.find("=}").map(|end| end + 4),
+            None => rest.find('}').map(|end| end + 1).filter(|end| {
+                !rest[1..end - 1].contains(|c: char| c.is_whitespace() || "{'\"\\".contains(c))
+            }),
+        };
+        match end {
+            Some(end) => {
+                if !text.is_empty() {
+                    quoted.push_str(&quote_always(&std::mem::take(&mut text)));
+                }
+                quoted.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() || quoted.is_empty() {
+        quoted.push_str(&quote_always(&text));
+    }
+    quoted
+}
+
+/**
+ * Private function.
+ *
+ * Return the word quoted if it wouldn't be read as a single word by the grammar,
+ * as it is otherwise so it is still read by the rule of its option (ex: "now,fail=1").
+ */
+fn quote_if_needed(word: &str) -> String {
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        shell_quote(word)
+    } else {
+        String::from(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn request_test() {
+        assert_eq!(
+            request(&tokens(&[
+                "echo", ":::", "a b", "", "--help", ":::", "it's", ":::x"
+            ]))
+            .ok()
+            .unwrap(),
+            "echo ::: 'a b' '' --help ::: 'it'\\''s' ':::x'"
+        );
+        // the command is written as in the shell, the values of the options are single words
+        assert_eq!(
+            request(&tokens(&[
+                "--joblog",
+                "my jobs.log",
+                "--halt",
+                "now,fail=1",
+                "--rpl",
+                "{..} s/\\..*//",
+                "grep 'a b' {}",
+                "::::",
+                "files.txt",
+            ]))
+            .ok()
+            .unwrap(),
+            "--joblog 'my jobs.log' --halt now,fail=1 --rpl '{..} s/\\..*//' grep 'a b' {} :::: files.txt"
+        );
+        assert!(parse(
+            &request(&tokens(&["--joblog", "", "echo", ":::", "{}"]))
+                .ok()
+                .unwrap()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn syntax_error_test() {
+        match parse("echo 'a b ::: c") {
+            Err(ParseError::Syntax(error)) => {
+                assert_eq!(error.message, "unterminated quote");
+                assert_eq!(error.span, Some((5, 7)));
+            }
+            _ => panic!(),
+        }
+        assert!(parse("echo 'a b' ::: c").is_ok());
+    }
+
+    #[test]
+    fn request_options_test() {
+        // "--option=value", any case, and "--" before a command starting with a dash
+        assert_eq!(
+            request(&tokens(&[
+                "--JOBS=2",
+                "--client=127.0.0.1",
+                "8888",
+                "--halt=now,fail=1",
+                "--",
+                "--dry-run",
+                "{}",
+            ]))
+            .ok()
+            .unwrap(),
+            "--jobs 2 --client 127.0.0.1 8888 --halt now,fail=1 '--dry-run' {}"
+        );
+        assert_eq!(
+            request(&tokens(&["--", "echo", "--jobs", ":::", "--jobs"]))
+                .ok()
+                .unwrap(),
+            "echo --jobs ::: --jobs"
+        );
+        // the options are only read before the command, the next words belong to it
+        let line = request(&tokens(&["-j", "2", "make", "-j", "4", ":::", "all"]));
+        let line = line.ok().unwrap();
+        let mut pairs = parse(&line).unwrap();
+        let words: Vec<(Rule, &str)> = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .filter(|pair| pair.as_rule() != Rule::EOI)
+            .map(|pair| (pair.as_rule(), pair.as_str().trim()))
+            .collect();
+        assert_eq!(words[0], (Rule::options, "-j 2"));
+        assert_eq!(words[1], (Rule::commands, "make -j 4"));
+        let error = request(&tokens(&["--jobs", "2", "--keep-ordre", "echo"]));
+        assert!(matches!(&error, Err(ParseError::UnknownOption(_))));
+        assert_eq!(
+            error.err().unwrap().to_string(),
+            "unknown option --keep-ordre\n    --jobs 2 --keep-ordre echo\n             ^^^^^^^^^^^^\ndid you mean --keep-order?"
+        );
+        assert!(matches!(
+            request(&tokens(&["--dry-run=yes", "echo"])),
+            Err(ParseError::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            request(&tokens(&["--jobs"])),
+            Err(ParseError::MissingValue(_))
+        ));
+        // a wrong value isn't read as the command
+        match request(&tokens(&["--client", "host", "port", "echo"])) {
+            Err(ParseError::InvalidValue(error)) => assert_eq!(error.span, Some((9, 18))),
+            _ => panic!(),
+        }
+        match request(&tokens(&["--halt=never!", "echo"])) {
+            Err(ParseError::InvalidValue(error)) => assert_eq!(error.span, Some((0, 13))),
+            _ => panic!(),
+        }
+        // the words of the command aren't options
+        assert!(request(&tokens(&["grep", "--color", "{}", ":::", "a"])).is_ok());
+
+        // the short options keep their case, their value can be in the same word
+        assert_eq!(
+            request(&tokens(&["-j4", "-aargs.txt", "-q", "echo"]))
+                .ok()
+                .unwrap(),
+            "-j 4 -a args.txt -q 'echo'"
+        );
+        match request(&tokens(&["-J", "2", "echo"])) {
+            Err(ParseError::UnknownOption(error)) => {
+                assert_eq!(error.suggestion.as_deref(), Some("-j"))
+            }
+            _ => panic!(),
+        }
+        // an unknown short option isn't read as the command, unless it is given after "--"
+        match request(&tokens(&["-k", "echo", ":::", "a"])) {
+            Err(ParseError::UnknownOption(error)) => assert_eq!(error.span, Some((0, 2))),
+            _ => panic!(),
+        }
+        assert_eq!(
+            request(&tokens(&["--", "-j4 x", ":::", "a"])).ok().unwrap(),
+            "'-j4' x ::: a"
+        );
+        assert!(parse("'-j4' x ::: a").is_ok());
+    }
+
+    #[test]
+    fn request_words_test() {
+        // without a shell, each word of the command stays a single word, its targets are still read
+        assert_eq!(
+            request(&tokens(&[
+                "--no-shell",
+                "touch",
+                "my file",
+                "x{}.txt",
+                "{= s/a/b/ =}",
+                ":::",
+                "x"
+            ]))
+            .ok()
+            .unwrap(),
+            "--no-shell 'touch' 'my file' 'x'{}'.txt' {= s/a/b/ =} ::: x"
+        );
+        assert_eq!(
+            request(&tokens(&["-q", "--", "printf", "%s\\n", "{a b}"]))
+                .ok()
+                .unwrap(),
+            "-q 'printf' '%s\\n' '{a b}'"
+        );
+        assert!(parse("--no-shell 'touch' 'my file' 'x'{}'.txt' {= s/a/b/ =} ::: x").is_ok());
+        // the last of --no-shell and --shell wins
+        assert_eq!(
+            request(&tokens(&["--no-shell", "--shell", "echo", "a b"]))
+                .ok()
+                .unwrap(),
+            "--no-shell --shell echo a b"
+        );
+    }
 }
@@ -0,0 +1,102 @@
+use crate::core::interpreter::InterpretError;
+use crate::core::jobmanager::{OptionsError, ERROR_EXIT_CODE};
+use std::fmt;
+use std::io;
+
+/**
+ * The errors of rust_parallel, returned by its entry points (`Parallel`, `JobManager::exec`,
+ * the client and the server) so the programs embedding it decide what to do :
+ * - `NoArguments` - the command line is empty
+ * - `Command` - the command line is wrong, or the help is asked (`InterpretError::Help`)
+ * - `Options` - the execution options given to a job manager can't be used together
+ * - `Bind` - the server can't listen on its address
+ * - `Connect` - the client can't connect to the server at its address
+ * - `Exchange` - the exchange between the client and the server failed
+ * - `Remote` - the request failed on the server, with its message and its exit code
+ * - `Io` - an input/output error, ex: the temporary directory of the server can't be created
+ */
+#[derive(Debug)]
+pub enum Error {
+    NoArguments,
+    Command(InterpretError),
+    Options(OptionsError),
+    Bind(String, io::Error),
+    Connect(String, io::Error),
+    Exchange(String),
+    Remote(String, i32),
+    Io(io::Error),
+}
+
+impl Error {
+    /**
+     * Return the exit code of the program for this error, it can't be confused
+     * with a number of failed jobs.
+     */
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Command(error) => error.exit_code(),
+            Error::Remote(_, code) => *code,
+            _ => ERROR_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoArguments => write!(f, "Error : no command given"),
+            Error::Command(InterpretError::Parse(error)) => write!(f, "Error : {}", error),
+            Error::Command(InterpretError::InvalidExpression(diagnostic)) => {
+                write!(f, "Invalid expression : {}", diagnostic)
+            }
+            Error::Command(error) => write!(f, "{}", error),
+            Error::Options(error) => write!(f, "{}", error),
+            Error::Bind(address, error) => {
+                write!(
+                    f,
+                    "Error : can't listen on the address {} : {}",
+                    address, error
+                )
+            }
+            Error::Connect(address, error) => write!(
+                f,
+                "Error : can't connect to the server {} : {}",
+                address, error
+            ),
+            Error::Exchange(reason) => write!(
+                f,
+                "Error : the exchange with the server failed : {}",
+                reason
+            ),
+            Error::Remote(message, _) => write!(f, "{}", message),
+            Error::Io(error) => write!(f, "Error : {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bind(_, error) | Error::Connect(_, error) | Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<InterpretError> for Error {
+    fn from(error: InterpretError) -> Error {
+        Error::Command(error)
+    }
+}
+
+impl From<OptionsError> for Error {
+    fn from(error: OptionsError) -> Error {
+        Error::Options(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
@@ -1,4 +1,5 @@
 pub mod core;
+pub mod error;
 pub mod parallel;
 pub mod remote;
 
@@ -1,22 +1,98 @@
+use rust_parallel::core::interpreter::InterpretError;
+use rust_parallel::core::jobmanager::{exit_code, user_shell};
+use rust_parallel::error::Error;
 use rust_parallel::parallel::Parallel;
 use std::env;
+use std::process;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use tokio::runtime::Builder;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+/// The signal stopping the program (SIGINT or SIGTERM), 0 until one is received.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/**
+ * Listen SIGINT and SIGTERM in a thread of its own and return the receiver interrupting the jobs.
+ * With the first signal, the running jobs are killed and the program stops once they are.
+ * With another one, or if no job can be interrupted (ex: with --server), it stops at once.
+ * Return None if the signals can't be listened, they stop the program as usual.
+ */
+fn listen_signals() -> Option<watch::Receiver<bool>> {
+    let runtime = Builder::new_current_thread().enable_all().build().ok()?;
+    let (mut interrupt, mut terminate) = {
+        let _context = runtime.enter();
+        (
+            signal(SignalKind::interrupt()).ok()?,
+            signal(SignalKind::terminate()).ok()?,
+        )
+    };
+    let (sender, receiver) = watch::channel(false);
+    thread::spawn(move || {
+        runtime.block_on(async {
+            loop {
+                let signal = tokio::select! {
+                    _ = interrupt.recv() => libc::SIGINT,
+                    _ = terminate.recv() => libc::SIGTERM,
+                };
+                if SIGNAL.swap(signal, Ordering::SeqCst) != 0 || sender.send(true).is_err() {
+                    stop(signal);
+                }
+            }
+        })
+    });
+    Some(receiver)
+}
+
+/**
+ * Stop the program as if it didn't catch the signal.
+ */
+fn stop(signal: i32) {
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
 
 fn main() {
     #[cfg(debug_assertions)]
     env_logger::init();
 
-    let shell = match env::var("SHELL") {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("couldn't interpret environment variable SHELL: {}", e);
-            return;
-        }
-    };
+    let shell = user_shell();
 
     let args: Vec<String> = env::args().skip(1).collect();
 
-    let prg = Parallel::new(shell, args);
-    prg.start();
+    let interrupt = listen_signals();
+
+    // the exit code tells how many jobs failed
+    let code = match Parallel::new(shell, args).and_then(|mut parallel| {
+        if let Some(interrupt) = interrupt {
+            parallel.set_interrupt(interrupt);
+        }
+        parallel.start()
+    }) {
+        Ok(results) => exit_code(&results),
+        Err(error) => {
+            match &error {
+                Error::NoArguments | Error::Command(InterpretError::Help) => {
+                    Parallel::print_usage()
+                }
+                Error::Command(_) => {
+                    eprintln!("{}", error);
+                    eprintln!("See rust_parallel --help for the usage.");
+                }
+                _ => eprintln!("{}", error),
+            }
+            error.exit_code()
+        }
+    };
+    // the jobs are killed, the program stops with the signal which interrupted them
+    let signal = SIGNAL.load(Ordering::SeqCst);
+    if signal != 0 {
+        stop(signal);
+    }
+    process::exit(code);
 }
 
 #[cfg(test)]
@@ -34,8 +110,12 @@ mod tests {
             String::from("World"),
         ];
 
-        let prg = Parallel::new(String::from("/bin/bash"), args);
+        let prg = Parallel::new(String::from("/bin/bash"), args).unwrap();
 
-        prg.start();
+        assert_eq!(exit_code(&prg.start().unwrap()), 0);
+        assert!(matches!(
+            Parallel::new(String::from("/bin/bash"), Vec::new()),
+            Err(Error::NoArguments)
+        ));
     }
 }
@@ -1,14 +1,16 @@
 // #![feature(external_doc)] // for #[doc(include="../README.md")] line 59
 use crate::core::interpreter;
-use crate::core::jobmanager::JobManager;
-use crate::core::parser;
+use crate::core::job::JobOutput;
+use crate::core::jobmanager::{JobManager, OutputMode};
+use crate::error::Error;
 use log::debug;
-use std::process;
+use tokio::sync::watch;
 
 /**
  * Entry point of the parallel program :
  * - `job_manager: JobManager` - allows you to manage the execution of the command
- * - `command: String` - list of parameters that we concatenate into a single String
+ * - `args: Vec<String>` - the words of the command line, each one is kept as a single word (ex: a value with spaces)
+ * - `output: OutputMode` - the outputs of the jobs are displayed or only returned by `start`
  * # Example
  * ```rust
  * use rust_parallel::parallel::Parallel;
@@ -18,13 +20,15 @@ use std::process;
  *           String::from(":::"),
  *           String::from("Hello"),
  *           String::from("World"),];
- * let mut prg = Parallel::new(String::from("/bin/bash"),args);
- * prg.start();
+ * let prg = Parallel::new(String::from("/bin/bash"), args).unwrap();
+ * let results = prg.start().unwrap();
+ * assert_eq!(results.len(), 2);
  * ```
  */
 pub struct Parallel {
     job_manager: JobManager,
-    command: String,
+    args: Vec<String>,
+    output: OutputMode,
 }
 
 impl Parallel {
@@ -34,36 +38,65 @@ impl Parallel {
      * # Attributs
      * - `shell: String` - the shell from the given environment, will be used to launch jobs
      * - `args: Vec<String>` - word table representing the command to be executed
+     *
+     * Return `Error::NoArguments` if the command line is empty.
      */
-    pub fn new(shell: String, args: Vec<String>) -> Parallel {
-        if args.len() == 0 {
-            Parallel::print_usage();
-            process::exit(1);
+    pub fn new(shell: String, args: Vec<String>) -> Result<Parallel, Error> {
+        if args.is_empty() {
+            return Err(Error::NoArguments);
         }
 
         let job_manager: JobManager = JobManager::new(shell);
-        let mut command = String::from("");
-        for arg in args {
-            command.push_str(&arg);
-            command.push(' ');
-        }
-
-        Parallel {
+        Ok(Parallel {
             job_manager,
-            command,
-        }
+            args,
+            output: OutputMode::Display,
+        })
+    }
+
+    /**
+     * Allows to stop the execution before its end, see `JobManager::set_interrupt`.
+     * # Attributs
+     * - `interrupt: watch::Receiver<bool>` - the running jobs are killed once its value is true
+     */
+    pub fn set_interrupt(&mut self, interrupt: watch::Receiver<bool>) {
+        self.job_manager.set_interrupt(interrupt);
     }
 
     /**
-     * Display help.
+     * Allows to forbid reading the values from the standard input, see `JobManager::set_stdin`.
+     * # Attributs
+     * - `stdin: bool` - true if the standard input can be read, by default
+     */
+    pub fn set_stdin(&mut self, stdin: bool) {
+        self.job_manager.set_stdin(stdin);
+    }
+
+    /**
+     * Allows to only return the outputs of the jobs instead of displaying them, see `OutputMode`.
+     * The command line has no option for it, so it is kept whatever the command line is.
+     * # Attributs
+     * - `output: OutputMode` - the output mode, `OutputMode::Display` by default
+     */
+    pub fn set_output(&mut self, output: OutputMode) {
+        self.output = output;
+    }
+
+    /**
+     * Display help, the usage of the program and its options.
      */
     // #[doc(include="../README.md")] // currently not available
-    fn print_usage() {
+    pub fn print_usage() {
         println!("RUST PARALLEL");
         println!("\nUSAGE:");
         println!("\trust_parallel [options] [command [arguments | {{[n]}}]] ::: values");
+        println!("\trust_parallel [options] [command [arguments | {{[n]}}]] :::: files");
+        println!("\tvalues | rust_parallel [options] [command [arguments | {{}}]]");
 
         println!("\nOPTIONS :");
+        println!(
+            "\tgiven before the command, as --option VALUE or --option=VALUE, -- ends the options"
+        );
         print!("\t--help ");
         println!("\t\t\tdisplay this message");
 
@@ -80,52 +113,121 @@ impl Parallel {
         println!("\t\tdisplay the output of the commands in the execution order given in input");
 
         print!("\t--jobs NB / -j NB");
-        println!("\tthe number of threads (NB) to be used (0 = unlimited)");
+        println!("\tthe number of jobs (NB) running at the same time (0 = unlimited, default = number of CPUs),");
+        println!("\t\t\t\t+NB, -NB and NB% are relative to the number of CPUs");
+
+        print!("\t--arg-file FILE / -a FILE");
+        println!(
+            "\tread the values of an input source from FILE, one per line (same as :::: FILE)"
+        );
+
+        print!("\t--link");
+        println!("\t\t\tlink the values of all the input sources instead of combining them (:::+ and ::::+ link only one)");
+
+        print!("\t--halt WHEN,COND=NB");
+        println!("\tstop once NB jobs (or NB%) ended with COND (fail or success), WHEN is now (kill the running jobs) or soon");
+
+        print!("\t--timeout SECS");
+        println!("\t\tkill the jobs running for more than SECS seconds (or NB% of the median runtime of the completed jobs)");
+
+        print!("\t--retries NB");
+        println!("\t\texecute again up to NB times the jobs which failed or timed out (the attempt is in $PARALLEL_ATTEMPT)");
+
+        print!("\t--retry-delay SECS");
+        println!("\twait SECS seconds before executing again a failed job");
+
+        print!("\t--retry-backoff");
+        println!("\t\tdouble the retry delay after each failed attempt");
+
+        print!("\t--joblog FILE");
+        println!("\t\trecord each executed job in FILE (tab separated: Seq, Host, Starttime, JobRuntime, Send, Receive, Exitval, Signal, Command)");
+
+        print!("\t--resume");
+        println!("\t\twith --joblog, skip the jobs already executed according to the joblog");
+
+        print!("\t--resume-failed");
+        println!("\twith --joblog, skip the jobs which already succeeded according to the joblog");
+
+        print!("\t--retry-failed");
+        println!("\t\twith --joblog, execute again the commands of the joblog which failed (the command line is ignored)");
 
         print!("\t--pipe ");
-        println!("\t\t\tis not yet implemented");
+        println!("\t\t\tsplit the standard input into blocks, each block is given to a job on its standard input");
+
+        print!("\t--block SIZE");
+        println!("\t\tthe size of the blocks with --pipe, ex: 10k, 1M (default 1M)");
+
+        print!("\t--quote / -q");
+        println!("\t\tquote the command too, the values are always quoted for the shell");
+
+        print!("\t--no-shell");
+        println!(
+            "\t\texecute the command without any shell, its words and the values are the arguments"
+        );
+        println!("\t\t\t(by default for the commands which don't need the shell)");
+
+        print!("\t--shell");
+        println!("\t\talways execute the command by $SHELL (or /bin/sh)");
+
+        print!("\t--rpl 'TAG EXPR'");
+        println!("\tdefine the replacement string TAG (ex: {{..}}) transformed by EXPR (as {{= EXPR =}}),");
+        println!(
+            "\t\t\talso read from the --rpl lines of ~/.parallel/config (or $PARALLEL_HOME/config)"
+        );
+
+        println!("\nREPLACEMENT STRINGS :");
+        println!("\t{{}}\t\tthe values of the job (they are appended to the command without any replacement string)");
+        println!("\t{{.}}\t\tthe values without their extension");
+        println!("\t{{/}}\t\tthe values without their directory");
+        println!("\t{{//}}\t\tthe directory of the values");
+        println!("\t{{/.}}\t\tthe values without their directory and their extension");
+        println!("\t{{#}}\t\tthe sequence number of the job, from 1");
+        println!("\t{{%}}\t\tthe job slot, from 1 to the number of jobs running at the same time");
+        println!("\t{{n}} {{n.}} ...\tthe value of the n-th input source, with the same variants");
+        println!("\t{{= EXPR =}}\tthe values transformed by EXPR ({{=n EXPR =}} for the n-th value), statements separated by ';' :");
+        println!("\t\t\ts/REGEX/REPL/[gi], uc, lc, substr(START[,LEN]), pad(WIDTH[,CHAR]),");
+        println!(
+            "\t\t\tor arithmetic with + - * / % ( ), $_ (the value) and $# (the sequence number)"
+        );
 
         println!("\nEXAMPLES :");
         println!("\tparallel echo ::: a b c ::: 1 2 3");
-        println!("\tparallel echo {} {}::: a b c ::: 1 2 3", "{2}", "{1}");
+        println!("\tparallel echo {{2}} {{1}} ::: a b c ::: 1 2 3");
+        println!("\tparallel echo ::: a b c :::: numbers.txt");
+        println!("\tparallel convert {{}} {{.}}.png ::: *.jpg");
+        println!("\tparallel mv {{1}} {{2}}.old ::: a b c :::+ x y z");
+        println!("\tparallel mv {{}} {{= s/\\.jpeg$/.jpg/i =}} ::: *.jpeg");
+        println!("\tparallel cp {{}} backup_{{= $#; pad(4,0) =}} ::: *.txt");
+        println!(
+            "\tparallel --rpl '{{..}} s/\\.[^.]+\\.[^.]+$//' tar xf {{}} -C {{..}} ::: *.tar.gz"
+        );
+        println!("\tparallel --timeout 200% ./simulation.sh ::: 1 2 3 4");
+        println!("\tparallel --retries 3 --retry-delay 1 --retry-backoff curl -fO ::: url1 url2");
+        println!("\tparallel --resume-failed --joblog jobs.log ./batch.sh ::: 1 2 3 4");
+        println!("\tparallel --halt now,success=1 grep -q secret ::: a.txt b.txt c.txt");
+        println!("\tfind . -name '*.log' | parallel gzip {{}}");
+        println!("\tcat file.log | parallel --pipe --block 10M wc -l");
         print!("\n\n");
     }
 
     /**
      * Parse the input command and configure the job manager with all the commands and execution options.
+     * Return the raw output of each job executed locally (none with --dry-run or a remote execution),
+     * or the error preventing the execution, ex: `Error::Command` for a wrong command line.
      */
-    pub fn start(mut self) -> Option<Vec<String>> {
+    pub fn start(mut self) -> Result<Vec<JobOutput>, Error> {
         // first let's store our request
-        self.job_manager.set_request(self.command.clone());
-
-        // let's try to parse our command
-        let mut result = match parser::parse(self.command.as_str()) {
-            Ok(result) => result,
-            Err(error) => {
-                eprintln!("Error : {}", error);
-                Parallel::print_usage();
-                process::exit(1);
-            }
-        };
-
-        // now that the command has been parse, we give the result to create jobs
-        match interpreter::interpret(&mut self.job_manager, &mut result) {
-            Err(error) => {
-                match error {
-                    interpreter::InterpretError::Help => (),
-                    interpreter::InterpretError::NoData(string) => eprintln!("{}", string),
-                    interpreter::InterpretError::BothSourceAndRemote(string) => {
-                        eprintln!("{}", string)
-                    }
-                }
-                Parallel::print_usage();
-                process::exit(1);
-            }
-            _ => (),
-        }
+        self.job_manager.set_request(self.args.clone());
+
+        // let's try to parse our command and to create the jobs, the words keep their boundaries
+        interpreter::interpret_args(&mut self.job_manager, &self.args)?;
+        // the output mode is added to the execution options of the command line
+        let mut options = self.job_manager.options().clone();
+        options.output(self.output);
+        self.job_manager.set_options(&options)?;
 
         // If everything is OK, we tell the jobmanager to start the execution.
         debug!("Parallel starts with => {}", self.job_manager);
-        return self.job_manager.exec();
+        self.job_manager.exec()
     }
 }
@@ -122,10 +122,7 @@ impl<'a> Channel<'a> {
      */
     pub fn send(&mut self, buf: Vec<u8>) {
         self.write_buf = buf;
-        debug!(
-            "Sending : {}",
-            String::from_utf8(self.write_buf.clone()).unwrap()
-        );
+        debug!("Sending : {}", String::from_utf8_lossy(&self.write_buf));
     }
 
     /**
@@ -155,7 +152,11 @@ impl<'a> Channel<'a> {
         let mut size_sent = 0;
 
         while self.running {
-            self.ready = self.socket.ready(self.interest).await.unwrap();
+            self.ready = self
+                .socket
+                .ready(self.interest)
+                .await
+                .map_err(|e| e.to_string())?;
 
             if self.ready.is_readable() {
                 // Vec size can be modified to read more bytes
@@ -6,15 +6,19 @@ use std::io::Read;
 
 use log::debug;
 
+use crate::core::job::JobOutput;
+use crate::error::Error;
 use crate::remote::channel::*;
+use crate::remote::decode_result;
 
 /**
  * Client side of a Parallel Client-Server exchange
  * - `request : String` - Request that we want to execute
  * - `files : Vec<String>` - List of file needed for the request's execution
- * - `request_response : String` - Use to store the request's result sent
- * by the Server
+ * - `request_response : Vec<JobOutput>` - Use to store the request's result sent
+ *   by the Server (the exit status, the raw standard output and standard error of each job)
  * - `server_address : String` - Server address, example : "127.0.0.1:8080"
+ * - `error : Option<Error>` - Error which ended the exchange early, if any
  */
 pub struct ParallelClient {
     // Request that our Client is going to send
@@ -24,10 +28,13 @@ pub struct ParallelClient {
     files: Vec<String>,
 
     // Request response sent by the server
-    request_response: String,
+    request_response: Vec<JobOutput>,
 
     // Server addresse using this format "[address:port]"
     server_address: String,
+
+    // Error which ended the exchange, the listener can't return it
+    error: Option<Error>,
 }
 
 /**
@@ -46,8 +53,9 @@ impl ParallelClient {
 
             files: Vec::new(),
 
-            request_response: String::new(),
+            request_response: Vec::new(),
             server_address,
+            error: None,
         }
     }
 
@@ -66,27 +74,27 @@ impl ParallelClient {
      * in order to launch the exchange_loop()
      *
      * Will return an Ok() result containing the request's execution
-     * result
+     * result, the output of each job executed by the server
      *
-     * If an error occured, will return an Err() result containing
-     * a String describing the kind of error
+     * If an error occured, will return an Err() result describing
+     * the kind of error : `Error::Connect` if the server can't be reached,
+     * `Error::Io` if a file can't be read, `Error::Exchange` if the exchange failed
+     * and `Error::Remote` if the request failed on the server
      */
-    pub async fn start_client(&mut self) -> Result<String, String> {
+    pub async fn start_client(&mut self) -> Result<Vec<JobOutput>, Error> {
         // Try to connect to the Server
         let res_connection = TcpStream::connect(self.server_address.clone()).await;
 
         let mut channel: Channel;
 
-        if let Ok(s) = res_connection {
-            debug!("Connection was a success");
-            // Creation of the channel with the socket returned
-            // by the TcpStream::connect method
-            channel = Channel::new(s);
-        } else {
-            panic!(
-                "Couldn't connect to the server, server address : {}",
-                self.server_address
-            );
+        match res_connection {
+            Ok(s) => {
+                debug!("Connection was a success");
+                // Creation of the channel with the socket returned
+                // by the TcpStream::connect method
+                channel = Channel::new(s);
+            }
+            Err(e) => return Err(Error::Connect(self.server_address.clone(), e)),
         }
 
         // If the list of files needed by the server is empty we can
@@ -101,9 +109,15 @@ impl ParallelClient {
         // Preparation of the Channel
         channel.set_listener(self);
         channel.set_interest(Interest::WRITABLE);
-        channel.exchange_loop().await.unwrap();
+        let exchange = channel.exchange_loop().await;
 
-        Ok(self.request_response.clone())
+        // The error of the client stopped the exchange, it explains why it failed
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        exchange.map_err(Error::Exchange)?;
+
+        Ok(std::mem::take(&mut self.request_response))
     }
 }
 
@@ -116,8 +130,9 @@ impl ChannelListener for ParallelClient {
      * interest to Write
      */
     fn received(&mut self, buffer: Vec<u8>) -> Option<Vec<u8>> {
-        // Convert the received buffer into a String using an utf-8 format
-        let response: String = String::from_utf8(buffer).unwrap();
+        // Convert the received buffer into a String using an utf-8 format,
+        // the result of the request is kept as it is because the jobs may output anything
+        let response: String = String::from_utf8_lossy(&buffer).into_owned();
         debug!("-- Client received : {}", response);
 
         let mut next_msg: Vec<u8> = Vec::new();
@@ -125,10 +140,13 @@ impl ChannelListener for ParallelClient {
         // Response "Ready for next file" means that we can now
         // send the file data
         if response.eq("Ready for next file") {
-            let mut file = File::open(self.files.remove(0)).unwrap();
-
-            if let Err(_e) = file.read_to_end(&mut next_msg) {
-                println!("Error reading the file");
+            // If the file can't be read, the exchange is stopped with this error
+            let read = File::open(self.files.remove(0))
+                .and_then(|mut file| file.read_to_end(&mut next_msg));
+            if let Err(e) = read {
+                debug!("Error reading the file : {}", e);
+                self.error = Some(Error::Io(e));
+                return None;
             }
 
             return Some(next_msg);
@@ -152,7 +170,15 @@ impl ChannelListener for ParallelClient {
             // If the response is not equal to any of the above test
             // we can assume that the server sent us the result of the
             // request execution
-            self.request_response = response;
+            match decode_result(&buffer) {
+                Some(Ok(outputs)) => self.request_response = outputs,
+                Some(Err(error)) => self.error = Some(error),
+                None => {
+                    self.error = Some(Error::Exchange(String::from(
+                        "Malformed result of the request",
+                    )))
+                }
+            }
             None
         }
     }
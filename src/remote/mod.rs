@@ -1,7 +1,90 @@
+use crate::core::job::JobOutput;
+use crate::error::Error;
+use std::convert::{TryFrom, TryInto};
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
 pub mod channel;
 pub mod client;
 pub mod server;
 
+/// Separates the words of the command line in a request sent to a server,
+/// a word of a command line can't contain it.
+pub const REQUEST_SEPARATOR: char = '\u{0}';
+
+/// Returns the result of a request sent by a server to its client, so the client displays
+/// each output where the job wrote it and exits like a local execution. The first byte tells
+/// whether the jobs were executed (0) or the request failed (1). In the first case, each job
+/// follows : whether it has an exit status (1 byte), its raw exit status (4 bytes, big endian),
+/// the length of its standard output (8 bytes, big endian), its standard output, the length of
+/// its standard error and its standard error. In the second one, the exit code of the error
+/// (4 bytes, big endian) is followed by its message.
+pub fn encode_result(result: &Result<Vec<JobOutput>, Error>) -> Vec<u8> {
+    match result {
+        Ok(outputs) => {
+            let mut encoded = vec![0];
+            for output in outputs {
+                let status = output.status.map(ExitStatus::into_raw);
+                encoded.push(status.is_some() as u8);
+                encoded.extend(&status.unwrap_or_default().to_be_bytes());
+                for bytes in &[&output.stdout, &output.stderr] {
+                    encoded.extend(&(bytes.len() as u64).to_be_bytes());
+                    encoded.extend(bytes.iter());
+                }
+            }
+            encoded
+        }
+        Err(error) => {
+            let mut encoded = vec![1];
+            encoded.extend(&error.exit_code().to_be_bytes());
+            encoded.extend(error.to_string().bytes());
+            encoded
+        }
+    }
+}
+
+/// Returns the outputs of the jobs, or the error of the request (`Error::Remote`), from the
+/// result of a request (see `encode_result`), None if the result is malformed.
+pub fn decode_result(result: &[u8]) -> Option<Result<Vec<JobOutput>, Error>> {
+    let (kind, mut result) = result.split_first()?;
+    match kind {
+        0 => {
+            let mut outputs = Vec::new();
+            while !result.is_empty() {
+                let has_status = *take(&mut result, 1)?.first()? != 0;
+                let status = i32::from_be_bytes(take(&mut result, 4)?.try_into().ok()?);
+                let mut bytes = Vec::new();
+                for _ in 0..2 {
+                    let length = u64::from_be_bytes(take(&mut result, 8)?.try_into().ok()?);
+                    bytes.push(take(&mut result, usize::try_from(length).ok()?)?.to_vec());
+                }
+                let stderr = bytes.pop()?;
+                let stdout = bytes.pop()?;
+                outputs.push(JobOutput {
+                    stdout,
+                    stderr,
+                    status: Some(ExitStatus::from_raw(status)).filter(|_| has_status),
+                    ..Default::default()
+                });
+            }
+            Some(Ok(outputs))
+        }
+        1 => {
+            let code = i32::from_be_bytes(take(&mut result, 4)?.try_into().ok()?);
+            let message = String::from_utf8_lossy(result).into_owned();
+            Some(Err(Error::Remote(message, code)))
+        }
+        _ => None,
+    }
+}
+
+/// Removes the `length` first bytes of a result and returns them, None if it's shorter.
+fn take<'a>(result: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    let (taken, rest) = result.split_at_checked(length)?;
+    *result = rest;
+    Some(taken)
+}
+
 #[cfg(test)]
 
 /**
@@ -11,13 +94,18 @@ pub mod server;
  * concurrent execution could cause an issue
  */
 mod tests {
+    use crate::core::job::JobOutput;
+    use crate::error::Error;
     use crate::remote::client::*;
     use crate::remote::server::*;
+    use crate::remote::{decode_result, encode_result};
     use std::fs;
     use std::fs::File;
     use std::fs::OpenOptions;
     use std::io::Read;
     use std::io::Write;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
     use std::sync::Arc;
     use tokio::runtime::Runtime;
     use tokio::sync::Semaphore;
@@ -42,7 +130,7 @@ mod tests {
 
             tokio::spawn(async move {
                 let mut server: ParallelServer =
-                    ParallelServer::new(String::from("127.0.0.1:8888")).await;
+                    ParallelServer::new(String::from("127.0.0.1:8888")).await.unwrap();
                 mutex_server.add_permits(1);
                 server.waiting_request().await;
             });
@@ -119,7 +207,7 @@ mod tests {
 
             tokio::spawn(async move {
                 let mut server: ParallelServer =
-                    ParallelServer::new(String::from("127.0.0.1:8889")).await;
+                    ParallelServer::new(String::from("127.0.0.1:8889")).await.unwrap();
                 mutex_server.add_permits(1);
                 server.waiting_request().await;
             });
@@ -189,7 +277,7 @@ mod tests {
             println!("Lauching server thread");
             tokio::spawn(async move {
                 let mut server: ParallelServer =
-                    ParallelServer::new(String::from("127.0.0.1:8890")).await;
+                    ParallelServer::new(String::from("127.0.0.1:8890")).await.unwrap();
                 mutex_server.add_permits(1);
                 println!("Server going in waiting loop");
                 server.waiting_request().await;
@@ -260,7 +348,7 @@ mod tests {
             println!("Lauching server thread");
             tokio::spawn(async move {
                 let mut server: ParallelServer =
-                    ParallelServer::new(String::from("127.0.0.1:8891")).await;
+                    ParallelServer::new(String::from("127.0.0.1:8891")).await.unwrap();
                 mutex_server.add_permits(1);
                 println!("Server going in waiting loop");
                 server.waiting_request().await;
@@ -297,4 +385,51 @@ mod tests {
         fs::remove_dir_all("tmp").unwrap();
         fs::remove_file("tata").unwrap();
     }
+
+    // Test that the outputs of the result of a request are kept apart
+    #[test]
+    fn test_result_encoding() {
+        let outputs = vec![
+            JobOutput {
+                stdout: b"out\n".to_vec(),
+                stderr: vec![0xff, b'\n'],
+                status: Some(ExitStatus::from_raw(256)),
+                ..Default::default()
+            },
+            JobOutput::default(),
+        ];
+        let decoded = decode_result(&encode_result(&Ok(outputs.clone())));
+        assert_eq!(decoded.unwrap().unwrap(), outputs);
+        let decoded = decode_result(&encode_result(&Ok(Vec::new())));
+        assert_eq!(decoded.unwrap().unwrap(), Vec::new());
+
+        // the error of the request keeps its message and its exit code
+        let decoded = decode_result(&encode_result(&Err(Error::NoArguments))).unwrap();
+        if let Err(Error::Remote(message, code)) = decoded {
+            assert_eq!(message, Error::NoArguments.to_string());
+            assert_eq!(code, Error::NoArguments.exit_code());
+        } else {
+            panic!("Should have decoded the error of the request");
+        }
+        assert!(decode_result(b"Ready for request").is_none());
+    }
+
+    // Test that a client which can't reach its server returns an error
+    #[test]
+    fn test_connection_error() {
+        let rt = Runtime::new().unwrap();
+
+        rt.block_on(async {
+            // nothing listens on the port 9 (discard) of the loopback
+            let mut client: ParallelClient =
+                ParallelClient::new(String::from("127.0.0.1:9"), "echo ::: a".to_string());
+            let res = client.start_client().await;
+
+            if let Err(Error::Connect(address, _)) = res {
+                assert_eq!(address, "127.0.0.1:9");
+            } else {
+                panic!("Should have caught a connection error");
+            }
+        });
+    }
 }
@@ -1,9 +1,12 @@
+use crate::core::job::JobOutput;
+use crate::core::jobmanager::{user_shell, OutputMode};
+use crate::error::Error;
 use crate::parallel::Parallel;
+use crate::remote::{encode_result, REQUEST_SEPARATOR};
 use tokio::io::Interest;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
 
-use std::env;
 use std::fs::OpenOptions;
 use std::fs::{self, DirBuilder};
 use std::io::Write;
@@ -38,38 +41,44 @@ impl ParallelServer {
      * - `server_adresse : String` - Server address of the server we want
      * to create, need to follow this format : [serverAddress:serverPort]
      * Example : 127.0.0.1:8080
+     *
+     * Will return an Err() result if the server can't listen on the address (`Error::Bind`)
+     * or if the temporary directory can't be created (`Error::Io`)
      */
-    pub async fn new(server_address: String) -> Self {
+    pub async fn new(server_address: String) -> Result<Self, Error> {
         // Creation of the passive Socket
         let res_bind = TcpListener::bind(server_address.clone()).await;
-        let listener: TcpListener;
-
-        let tmp_dir = String::from("tmp/");
 
         // If the TcpListener::bind() method return an Ok result
         // it means that the bind succeed and that we can retrieve
         // the TcpListener
-        if let Ok(l) = res_bind {
-            listener = l;
-        } else {
-            panic!("Couldn't bind the server to the address {}", server_address);
-        }
+        let listener: TcpListener = match res_bind {
+            Ok(l) => l,
+            Err(e) => return Err(Error::Bind(server_address, e)),
+        };
+
+        let tmp_dir = String::from("tmp/");
 
         // Try to create the temporary directory
         // It is not an "actual" error if this returns an error AlreadyExists
         if let Err(e) = DirBuilder::new().create(tmp_dir.clone()) {
             if e.kind() != std::io::ErrorKind::AlreadyExists {
                 debug!("{:?}", e);
-                panic!("Couldn't create temporary directory");
+                return Err(Error::Io(e));
             } else {
                 debug!("Temporary dir already exists, {}", e);
             }
         }
 
         // Check that the tmp_dir is a directory
-        assert!(fs::metadata(tmp_dir.clone()).unwrap().is_dir());
+        if !fs::metadata(tmp_dir.clone())?.is_dir() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} isn't a directory", tmp_dir),
+            )));
+        }
 
-        ParallelServer { listener, tmp_dir }
+        Ok(ParallelServer { listener, tmp_dir })
     }
 
     /** Listening loop
@@ -119,7 +128,8 @@ pub enum WorkerState {
 /**
  * Client side of a Parallel Client-Server exchange
  * - `request : String` - Request that we want to execute
- * - `request_result : String` - Use to store the request's result
+ * - `request_result : Vec<JobOutput>` - Use to store the request's result (the exit status,
+ *   the raw standard output and standard error of each job, sent by `encode_result`)
  * - `tmp_dir : String` - Path of the temporary directory used
  * during the exchange files phase (if needed)
  * - `files : Vec<(String, String)>` - Name of the files sent by the Client
@@ -128,10 +138,11 @@ pub enum WorkerState {
  * if the tmp_dir variable is set to "/tmp/"
  * - `state : WorkerState` - Current state of the Worker
  * - `current_file : String` - Field used for the file exchange phase
+ * - `error : Option<Error>` - Error which ended the exchange early, if any
  */
 pub struct ParallelWorker {
     // Result of the request execution
-    request_result: String,
+    request_result: Vec<JobOutput>,
     // Request asked by the Client
     request: String,
 
@@ -148,6 +159,9 @@ pub struct ParallelWorker {
     state: WorkerState,
     // Name of the file currently transfered
     current_file: String,
+
+    // Error which ended the exchange, the listener can't return it
+    error: Option<Error>,
 }
 
 impl ParallelWorker {
@@ -159,12 +173,13 @@ impl ParallelWorker {
      */
     pub fn new(tmp_dir: String) -> Self {
         ParallelWorker {
-            request_result: String::new(),
+            request_result: Vec::new(),
             request: String::new(),
             tmp_dir,
             files: Vec::new(),
             state: WorkerState::Idle,
             current_file: String::new(),
+            error: None,
         }
     }
 
@@ -177,17 +192,18 @@ impl ParallelWorker {
      * socket
      * - `tmp_dir : String` - Path of the temporary directory used
      * during the exchange files phase (if needed)
+     *
+     * Will return an Err() result if the exchange failed (`Error::Exchange`)
+     * or if a file sent by the Client can't be saved (`Error::Io`)
      */
-    pub async fn process(socket: TcpStream, tmp_dir: String) -> Result<String, String> {
+    pub async fn process(socket: TcpStream, tmp_dir: String) -> Result<Vec<JobOutput>, Error> {
         let mut worker = ParallelWorker::new(tmp_dir);
         let result_work = worker.start_worker(socket).await;
 
-        if let Ok(res) = result_work {
-            return Ok(res);
-        } else {
-            debug!("Error occured during worker job");
-            return result_work;
+        if let Err(error) = &result_work {
+            debug!("Error occured during worker job : {}", error);
         }
+        result_work
     }
 
     /**
@@ -197,7 +213,7 @@ impl ParallelWorker {
      * - `tmp_dir : String` - Path of the temporary directory used
      * during the exchange files phase (if needed)
      */
-    pub async fn start_worker(&mut self, socket: TcpStream) -> Result<String, String> {
+    pub async fn start_worker(&mut self, socket: TcpStream) -> Result<Vec<JobOutput>, Error> {
         // Channel creation
         let mut channel: Channel = Channel::new(socket);
 
@@ -206,9 +222,15 @@ impl ParallelWorker {
         channel.set_interest(Interest::READABLE);
 
         // Start of the exchange loop
-        channel.exchange_loop().await.unwrap();
+        let exchange = channel.exchange_loop().await;
+
+        // The error of the worker stopped the exchange, it explains why it failed
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        exchange.map_err(Error::Exchange)?;
 
-        Ok(self.request_result.clone())
+        Ok(std::mem::take(&mut self.request_result))
     }
 }
 
@@ -219,7 +241,9 @@ impl ChannelListener for ParallelWorker {
      * interest to Write
      */
     fn received(&mut self, buffer: Vec<u8>) -> Option<Vec<u8>> {
-        let response = String::from_utf8(buffer).unwrap();
+        // The files sent by the Client may contain anything, only the
+        // other messages are read as text
+        let response = String::from_utf8_lossy(&buffer).into_owned();
         debug!("-- Server received : {}", response);
 
         match &self.state {
@@ -283,28 +307,25 @@ impl ChannelListener for ParallelWorker {
                             .write(true)
                             .create_new(true)
                             .open(self.tmp_dir.clone() + &self.current_file + &extension[..]);
-                    // If the error isn't an AlreadyExists error, we'll panic
+                    // If the error isn't an AlreadyExists error, the exchange is stopped
                     } else {
-                        println!("{:?}", e);
-                        panic!(
-                            "Unexpected behaviour, couldn't create the temporary file for {}",
-                            self.current_file
-                        );
+                        break;
                     }
                 }
 
-                // The use of unwrap here is safe due to our previous while loop
-                let mut file = result.unwrap();
-
-                // Write the data on the file and sync it
-                let buf: &[u8] = &response.bytes().collect::<Vec<u8>>()[..];
-
-                if let Err(_) = file.write_all(buf) {
-                    panic!("Couldn't write the data in the temporary file");
-                }
-
-                if let Err(_) = file.sync_data() {
-                    panic!("Couldn't save the data in the temporary file");
+                // Create the file, write the data on it and sync it
+                // If one of them fails, the exchange is stopped with this error
+                let saved = result.and_then(|mut file| {
+                    file.write_all(&buffer)?;
+                    file.sync_data()
+                });
+                if let Err(e) = saved {
+                    debug!(
+                        "Couldn't save the temporary file for {} : {}",
+                        self.current_file, e
+                    );
+                    self.error = Some(Error::Io(e));
+                    return None;
                 }
 
                 // Push the couple (Filename, Filepath) in the vec contained in the
@@ -328,34 +349,40 @@ impl ChannelListener for ParallelWorker {
                 // put the request result in this variable
                 debug!("Server : request received : {}", self.request);
 
-                let shell = match env::var("SHELL") {
-                    Ok(val) => val,
-                    Err(e) => {
-                        eprintln!("Couldn't interpret environment variable SHELL: {}", e);
-                        return None;
-                    }
-                };
+                let shell = user_shell();
 
                 let args: Vec<String> = self
                     .request
-                    .split_whitespace()
+                    .split(REQUEST_SEPARATOR)
                     .map(|s| s.to_string())
                     .collect();
 
-                let prg = Parallel::new(shell, args);
-                self.request_result = String::new();
-                if let Some(results) = prg.start() {
-                    for result in results {
-                        self.request_result.push_str(&result);
-                    }
-                }
+                // an error of the request is sent to the client instead of stopping the server
+                // the outputs are only sent to the client, not displayed by the server,
+                // and the values can't be read from the standard input of the server
+                let result = Parallel::new(shell, args).and_then(|mut parallel| {
+                    parallel.set_output(OutputMode::Collect);
+                    parallel.set_stdin(false);
+                    parallel.start()
+                });
 
-                debug!("Server : result of request : {}", self.request_result);
+                match &result {
+                    Ok(outputs) => debug!("Server : {} jobs executed", outputs.len()),
+                    Err(error) => debug!("Server : request failed : {}", error),
+                }
+                let encoded = encode_result(&result);
+                self.request_result = result.unwrap_or_default();
 
                 self.state = WorkerState::SendingResult;
-                return Some(self.request_result.bytes().collect());
+                Some(encoded)
+            }
+            // The result has been sent, the Client isn't supposed to send anything else
+            WorkerState::SendingResult => {
+                self.error = Some(Error::Exchange(String::from(
+                    "Unexpected message after the result of the request",
+                )));
+                None
             }
-            _ => panic!("Shoudln't happened"),
         }
     }
 